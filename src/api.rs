@@ -0,0 +1,318 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::NaiveDate;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::{
+    config::Settings,
+    dealer::DealerStatus,
+    element::{Element, MarketElement},
+    metrics::METRICS,
+    money::{decimal, Money},
+    resources::GameData,
+    state::GameState,
+    utils::{get_route_price, normalize_name},
+};
+
+#[derive(Clone, Serialize)]
+pub struct ApiMarketElement {
+    pub name: String,
+    pub supply: usize,
+    pub demand: usize,
+    #[serde(with = "decimal")]
+    pub price: Money,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ApiMarket {
+    pub location: String,
+    pub drugs: Vec<ApiMarketElement>,
+    pub items: Vec<ApiMarketElement>,
+}
+
+/// What anyone in the channel can tell about a dealer.
+#[derive(Clone, Serialize)]
+pub struct ApiDealer {
+    pub nick: String,
+    pub status: DealerStatus,
+    /// Unset while flying.
+    pub location: Option<String>,
+    pub health: f32,
+    #[serde(with = "decimal")]
+    pub laundered_money: Money,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ApiLeaderboardEntry {
+    pub place: usize,
+    pub nick: String,
+    #[serde(with = "decimal")]
+    pub laundered_money: Money,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ApiFlightPrice {
+    pub destination: String,
+    pub mode: String,
+    #[serde(with = "decimal")]
+    pub price: Money,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ApiDay {
+    pub day: NaiveDate,
+    /// Real seconds an in-game day lasts.
+    pub day_duration: u32,
+}
+
+/// The game as the API serves it, refreshed from the bot every second.
+#[derive(Default)]
+pub struct ApiSnapshot {
+    pub day: Option<ApiDay>,
+    pub markets: Vec<ApiMarket>,
+    pub dealers: Vec<ApiDealer>,
+    pub leaderboard: Vec<ApiLeaderboardEntry>,
+    /// What `f` charges from every location, by each mode that gets there.
+    /// Only the config sets those, they don't change while the game runs.
+    pub flight_prices: BTreeMap<String, Vec<ApiFlightPrice>>,
+}
+
+/// Shared between the bot, which publishes, and the HTTP listener.
+#[derive(Clone, Default)]
+pub struct Api(pub Arc<RwLock<ApiSnapshot>>);
+
+fn api_market<E: Element>(
+    market: &HashMap<Arc<E>, MarketElement>,
+    day_duration: u32,
+) -> Vec<ApiMarketElement> {
+    market
+        .iter()
+        .map(|(elem, market_elem)| ApiMarketElement {
+            name: elem.name().to_owned(),
+            supply: market_elem.supply,
+            demand: market_elem.demand,
+            price: market_elem.current_price(day_duration),
+        })
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+        .collect()
+}
+
+impl Api {
+    pub fn new(game_data: &GameData) -> Self {
+        let flight_prices = game_data
+            .locations
+            .iter()
+            .map(|origin| {
+                let prices = game_data
+                    .locations
+                    .iter()
+                    .filter(|destination| destination != &origin)
+                    .cartesian_product(game_data.travel_modes.iter())
+                    .filter_map(|(destination, mode)| {
+                        let route = game_data.routes.find(origin, destination, mode)?;
+
+                        Some(ApiFlightPrice {
+                            destination: destination.name.clone(),
+                            mode: mode.name.clone(),
+                            price: get_route_price(origin, &route, mode),
+                        })
+                    })
+                    .collect();
+
+                (origin.name.clone(), prices)
+            })
+            .collect();
+
+        Self(Arc::new(RwLock::new(ApiSnapshot {
+            flight_prices,
+            ..Default::default()
+        })))
+    }
+
+    pub fn publish(&self, settings: &Settings, game_data: &GameData, state: &GameState) {
+        let markets = game_data
+            .locations
+            .iter()
+            .map(|location| {
+                let data = state.locations.get(location).unwrap();
+
+                ApiMarket {
+                    location: location.name.clone(),
+                    drugs: api_market(&data.drug_market, settings.day_duration),
+                    items: api_market(&data.item_market, settings.day_duration),
+                }
+            })
+            .collect();
+
+        let dealers = state
+            .dealers
+            .values()
+            .map(|dealer| ApiDealer {
+                nick: dealer.nick.clone(),
+                status: dealer.status.clone(),
+                location: match dealer.status {
+                    DealerStatus::Flying => None,
+                    _ => Some(dealer.location.name.clone()),
+                },
+                health: dealer.health,
+                laundered_money: dealer.laundered_money.clone(),
+            })
+            .sorted_by(|a, b| a.nick.cmp(&b.nick))
+            .collect();
+
+        let leaderboard = state
+            .dealers
+            .leaderboard(state.dealers.len())
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (nick, laundered_money))| ApiLeaderboardEntry {
+                place: idx + 1,
+                nick,
+                laundered_money,
+            })
+            .collect();
+
+        let mut snapshot = self.0.write().unwrap();
+
+        snapshot.day = Some(ApiDay {
+            day: settings.current_day,
+            day_duration: settings.day_duration,
+        });
+        snapshot.markets = markets;
+        snapshot.dealers = dealers;
+        snapshot.leaderboard = leaderboard;
+    }
+}
+
+fn not_found(what: &str) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({ "error": format!("no such {}", what) })),
+    )
+        .into_response()
+}
+
+/// Locations are looked up by name ignoring case and punctuation, or by the
+/// start of it.
+fn find_location<'a, T>(
+    entries: impl Iterator<Item = (&'a String, T)> + Clone,
+    wanted: &str,
+) -> Option<T> {
+    let wanted = normalize_name(wanted);
+
+    entries
+        .clone()
+        .find(|(name, _)| normalize_name(name) == wanted)
+        .or_else(|| {
+            entries
+                .filter(|(name, _)| normalize_name(name).starts_with(&wanted))
+                .exactly_one()
+                .ok()
+        })
+        .map(|(_, entry)| entry)
+}
+
+async fn day(State(api): State<Api>) -> Response {
+    match &api.0.read().unwrap().day {
+        Some(day) => Json(day.clone()).into_response(),
+        None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+async fn markets(State(api): State<Api>) -> Response {
+    Json(api.0.read().unwrap().markets.clone()).into_response()
+}
+
+async fn market(State(api): State<Api>, Path(location): Path<String>) -> Response {
+    let snapshot = api.0.read().unwrap();
+
+    let markets = snapshot
+        .markets
+        .iter()
+        .map(|market| (&market.location, market));
+
+    match find_location(markets, &location) {
+        Some(market) => Json(market.clone()).into_response(),
+        None => not_found("location"),
+    }
+}
+
+async fn dealers(State(api): State<Api>) -> Response {
+    Json(api.0.read().unwrap().dealers.clone()).into_response()
+}
+
+async fn dealer(State(api): State<Api>, Path(nick): Path<String>) -> Response {
+    let snapshot = api.0.read().unwrap();
+
+    match snapshot.dealers.iter().find(|dealer| dealer.nick == nick) {
+        Some(dealer) => Json(dealer.clone()).into_response(),
+        None => not_found("dealer"),
+    }
+}
+
+async fn all_flights(State(api): State<Api>) -> Response {
+    Json(api.0.read().unwrap().flight_prices.clone()).into_response()
+}
+
+async fn flights(State(api): State<Api>, Path(location): Path<String>) -> Response {
+    let snapshot = api.0.read().unwrap();
+
+    match find_location(snapshot.flight_prices.iter(), &location) {
+        Some(prices) => Json(prices.clone()).into_response(),
+        None => not_found("location"),
+    }
+}
+
+async fn metrics() -> Response {
+    (
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        METRICS.render(),
+    )
+        .into_response()
+}
+
+async fn leaderboard(State(api): State<Api>) -> Response {
+    Json(api.0.read().unwrap().leaderboard.clone()).into_response()
+}
+
+pub fn router(api: Api) -> Router {
+    Router::new()
+        .route("/day", get(day))
+        .route("/markets", get(markets))
+        .route("/markets/:location", get(market))
+        .route("/dealers", get(dealers))
+        .route("/dealers/:nick", get(dealer))
+        .route("/flights", get(all_flights))
+        .route("/flights/:location", get(flights))
+        .route("/leaderboard", get(leaderboard))
+        .route("/metrics", get(metrics))
+        .with_state(api)
+}
+
+/// Serves the API until the bot stops. A listener that can't start is
+/// reported, the game goes on without it.
+pub async fn serve(address: SocketAddr, api: Api) {
+    let listener = match tokio::net::TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("couldn't start the API on {}: {}", address, err);
+            return;
+        }
+    };
+
+    if let Err(err) = axum::serve(listener, router(api)).await {
+        log::error!("the API stopped: {}", err);
+    }
+}
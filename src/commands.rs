@@ -0,0 +1,733 @@
+use std::sync::Arc;
+
+use chrono::{Duration, NaiveDate};
+use ircie::format::{Color, Msg};
+use rand::seq::IteratorRandom;
+
+use crate::{
+    dealer::Dealer,
+    element::Element,
+    error::{Error, Result},
+    event_log::{ElementKind, GameEvent},
+    frontend::Outgoing,
+    game::Game,
+    location_data::NEWS_ARCHIVE_DAYS,
+    money::Money,
+    render::{
+        render_admin_help, render_events, render_help, render_info, render_leaderboard,
+        render_market, render_news, render_people, render_price_history, render_prices_from,
+        render_travel_modes,
+    },
+    resources::{Drug, DrugWarsRng, GameData, Item, Matching, TravelMode},
+    save::SaveData,
+    snapshot::{keep_rolled_back, load_snapshot},
+    state::GameState,
+    utils::{get_date_and_time, parse_amount, parse_trade_arguments, PrettyAmount, PrettyMoney},
+};
+
+/// Runs a command for the dealer with that nick.
+pub type CommandFn = fn(&mut Game, &str, &[&str]) -> Result<Outgoing>;
+
+/// Every command, by the name it's typed as.
+pub const COMMANDS: &[(&str, CommandFn)] = &[
+    ("melp?", melp),
+    ("register", register),
+    ("i", dealer_info),
+    ("h", show_help),
+    ("m", show_market),
+    ("p", show_people),
+    ("cf", check_flight_prices),
+    ("f", fly_to),
+    ("stay", stay),
+    ("pack", pack),
+    ("unpack", unpack),
+    ("bribe", bribe),
+    ("t", show_time),
+    ("ph", show_price_history),
+    ("news", show_news),
+    ("leaderboard", show_leaderboard),
+    ("ha", show_admin_help),
+    ("save", save),
+    ("events", show_events),
+    ("rollback", rollback),
+    ("set", set_preference),
+    ("bd", buy::<Drug>),
+    ("sd", sell::<Drug>),
+    ("bi", buy::<Item>),
+    ("si", sell::<Item>),
+    ("cbd", check_trade_price::<Drug, true>),
+    ("csd", check_trade_price::<Drug, false>),
+    ("cbi", check_trade_price::<Item, true>),
+    ("csi", check_trade_price::<Item, false>),
+];
+
+fn register(game: &mut Game, nick: &str, _arguments: &[&str]) -> Result<Outgoing> {
+    let Game {
+        settings,
+        state,
+        game_data,
+        rng,
+        log,
+        ..
+    } = game;
+
+    if state.dealers.contains_key(nick) {
+        return Err(Error::AlreadyRegistered);
+    }
+
+    let location = game_data
+        .locations
+        .iter()
+        .choose(&mut rng.0)
+        .unwrap()
+        .clone();
+    let money = Money::from_dollars(100_000_000.);
+
+    log.record(
+        settings.current_day,
+        Some(nick),
+        GameEvent::Registered {
+            location: location.name.clone(),
+            money: money.clone(),
+        },
+    );
+
+    state.dealers.insert(
+        nick.to_owned(),
+        Dealer::new(nick, location, money, &game_data.locations),
+    );
+
+    Ok(Outgoing::to_sender([Msg::new()
+        .text(nick)
+        .text("Get Rich or Die Tryin")]))
+}
+
+fn dealer_info(game: &mut Game, nick: &str, _arguments: &[&str]) -> Result<Outgoing> {
+    let view = game.view(nick);
+    let Game { state, .. } = game;
+
+    let dealer = state.dealers.get_dealer(nick)?;
+    Ok(Outgoing::to_source(render_info(
+        &view,
+        dealer,
+        state.flights.get(nick),
+    )))
+}
+
+fn show_leaderboard(game: &mut Game, nick: &str, _arguments: &[&str]) -> Result<Outgoing> {
+    let view = game.view(nick);
+    let Game { state, .. } = game;
+
+    Ok(Outgoing::to_source(render_leaderboard(
+        &view,
+        &state.dealers.leaderboard(5),
+    )))
+}
+
+fn melp(_game: &mut Game, _nick: &str, _arguments: &[&str]) -> Result<Outgoing> {
+    Ok(Outgoing::to_source([Msg::new()
+        .text("explodes.")
+        .as_action()]))
+}
+
+pub fn show_help(game: &mut Game, nick: &str, _arguments: &[&str]) -> Result<Outgoing> {
+    Ok(Outgoing::to_source(render_help(&game.view(nick))))
+}
+
+fn show_admin_help(game: &mut Game, nick: &str, _arguments: &[&str]) -> Result<Outgoing> {
+    Ok(Outgoing::to_source(render_admin_help(&game.view(nick))))
+}
+
+fn show_market(game: &mut Game, nick: &str, _arguments: &[&str]) -> Result<Outgoing> {
+    let view = game.view(nick);
+    let Game {
+        settings, state, ..
+    } = game;
+
+    let (dealer, loc_data) = state.dealer_location(nick)?;
+
+    Ok(Outgoing::to_source(render_market(
+        settings, &view, nick, dealer, loc_data,
+    )))
+}
+
+fn show_news(game: &mut Game, nick: &str, arguments: &[&str]) -> Result<Outgoing> {
+    let view = game.view(nick);
+    let Game {
+        settings,
+        state,
+        game_data,
+        ..
+    } = game;
+
+    let days_ago = match arguments.len() {
+        0 => 0,
+        1 => arguments[0]
+            .parse::<usize>()
+            .map_err(|_| Error::InvalidNumber(arguments[0].to_owned()))?,
+        _ => return Err(Error::InvalidArguments),
+    };
+
+    if days_ago > NEWS_ARCHIVE_DAYS {
+        return Err(Error::InvalidArguments);
+    }
+
+    let news = game_data
+        .locations
+        .iter()
+        .map(|location| (location, state.locations.get(location).unwrap()))
+        .filter_map(|(location, data)| {
+            let headlines = match days_ago {
+                0 => Some(&data.news),
+                _ => data.news_archive.get(days_ago - 1),
+            }?;
+
+            match headlines.is_empty() {
+                true => None,
+                false => Some((location.as_ref(), headlines)),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let day = settings.current_day - Duration::days(days_ago as i64);
+
+    Ok(Outgoing::to_source(render_news(
+        &view,
+        &day.format("%Y-%m-%d").to_string(),
+        &news,
+    )))
+}
+
+fn show_people(game: &mut Game, nick: &str, _arguments: &[&str]) -> Result<Outgoing> {
+    let view = game.view(nick);
+    let Game { state, .. } = game;
+
+    let (_, loc_data) = state.dealer_location(nick)?;
+
+    Ok(Outgoing::to_source(render_people(&view, loc_data)))
+}
+
+fn check_flight_prices(game: &mut Game, nick: &str, arguments: &[&str]) -> Result<Outgoing> {
+    let view = game.view(nick);
+    let Game {
+        settings,
+        state,
+        game_data,
+        ..
+    } = game;
+
+    let dealer = state.dealers.get_dealer(nick)?;
+    let (destination, mode) = split_travel_mode(game_data, arguments);
+
+    if destination.is_empty() {
+        return Ok(Outgoing::to_source(render_prices_from(
+            settings,
+            &view,
+            &dealer.location,
+            &game_data.locations,
+            mode,
+            &game_data.travel_modes,
+            &game_data.routes,
+        )));
+    }
+
+    let destination = game_data.locations.get_matching(&destination)?;
+
+    Ok(Outgoing::to_source(render_travel_modes(
+        settings,
+        &view,
+        &dealer.location,
+        destination,
+        &game_data.travel_modes,
+        &game_data.routes,
+    )))
+}
+
+/// Takes a travel mode off the end of `arguments`, the words before it
+/// name the destination. Without one, the first mode is used.
+fn split_travel_mode<'a>(
+    game_data: &'a GameData,
+    arguments: &[&str],
+) -> (String, &'a Arc<TravelMode>) {
+    if let Some((last, rest)) = arguments.split_last() {
+        if let Some(mode) = game_data.travel_modes.get_exact(last) {
+            return (rest.join(" "), mode);
+        }
+    }
+
+    (arguments.join(" "), &game_data.travel_modes[0])
+}
+
+fn fly_to(game: &mut Game, nick: &str, arguments: &[&str]) -> Result<Outgoing> {
+    let Game {
+        settings,
+        state,
+        game_data,
+        log,
+        ..
+    } = game;
+
+    let state = &mut *state;
+    let dealer = state.dealers.get_dealer_available_mut(nick)?;
+
+    let (destination, mode) = split_travel_mode(game_data, arguments);
+
+    if destination.is_empty() {
+        return Err(Error::InvalidArguments);
+    }
+
+    let destination = game_data.locations.get_matching(&destination)?;
+
+    let current_location_data = state.locations.get_mut(&dealer.location).unwrap();
+    let (from, money) = (dealer.location.name.clone(), dealer.money.clone());
+
+    let lines = dealer.fly_to(
+        &mut state.flights,
+        &game_data.routes,
+        destination,
+        mode,
+        current_location_data,
+        settings.current_day,
+    )?;
+
+    let flight = state.flights.get(nick).unwrap();
+
+    log.record(
+        settings.current_day,
+        Some(nick),
+        GameEvent::Departed {
+            from,
+            destination: destination.name.clone(),
+            mode: mode.name.clone(),
+            price: money - &dealer.money,
+            arrival: flight.arrival,
+            via: flight
+                .layovers
+                .iter()
+                .map(|(location, _)| location.name.clone())
+                .collect(),
+        },
+    );
+
+    Ok(Outgoing::to_sender(lines))
+}
+
+fn stay(game: &mut Game, nick: &str, _arguments: &[&str]) -> Result<Outgoing> {
+    let Game {
+        settings,
+        state,
+        rng,
+        log,
+        ..
+    } = game;
+
+    let state = &mut *state;
+    let dealer = state.dealers.get_dealer_available_mut(nick)?;
+
+    let flight = match state.flights.get(&dealer.nick) {
+        Some(flight) if flight.layover.is_some() => state.flights.remove(&dealer.nick).unwrap(),
+        _ => return Err(Error::NotOnLayover),
+    };
+
+    let mut lines = vec![format!("you're staying in {}", dealer.location.name)];
+
+    log.record(
+        settings.current_day,
+        Some(nick),
+        GameEvent::Stayed {
+            location: dealer.location.name.clone(),
+        },
+    );
+
+    if let Some((event, msg)) = dealer.clear_customs(flight.cargo, &mut rng.0) {
+        log.record(settings.current_day, Some(nick), event);
+        lines.extend(msg.map(|msg| msg.to_string()));
+    }
+
+    Ok(Outgoing::to_sender(lines))
+}
+
+fn pack(game: &mut Game, nick: &str, arguments: &[&str]) -> Result<Outgoing> {
+    let Game {
+        settings,
+        state,
+        game_data,
+        log,
+        ..
+    } = game;
+
+    if arguments.len() != 2 {
+        return Err(Error::InvalidArguments);
+    }
+
+    let dealer = state.dealers.get_dealer_available_mut(nick)?;
+    let drug = game_data.drugs.get_matching(arguments[0])?;
+    let amount = parse_amount(arguments[1])?;
+
+    let packed = dealer.carry_on.get(drug).map_or(0, |owned| owned.amount);
+    let lines = dealer.pack(drug, &amount)?;
+
+    log.record(
+        settings.current_day,
+        Some(nick),
+        GameEvent::Packed {
+            drug: drug.name.clone(),
+            amount: dealer.carry_on[drug].amount - packed,
+        },
+    );
+
+    Ok(Outgoing::to_sender(lines))
+}
+
+fn unpack(game: &mut Game, nick: &str, arguments: &[&str]) -> Result<Outgoing> {
+    let Game {
+        settings,
+        state,
+        game_data,
+        log,
+        ..
+    } = game;
+
+    if arguments.len() != 2 {
+        return Err(Error::InvalidArguments);
+    }
+
+    let dealer = state.dealers.get_dealer_available_mut(nick)?;
+    let drug = game_data.drugs.get_matching(arguments[0])?;
+    let amount = parse_amount(arguments[1])?;
+
+    let packed = dealer.carry_on.get(drug).map_or(0, |owned| owned.amount);
+    let lines = dealer.unpack(drug, &amount)?;
+
+    log.record(
+        settings.current_day,
+        Some(nick),
+        GameEvent::Unpacked {
+            drug: drug.name.clone(),
+            amount: packed - dealer.carry_on.get(drug).map_or(0, |owned| owned.amount),
+        },
+    );
+
+    Ok(Outgoing::to_sender(lines))
+}
+
+fn bribe(game: &mut Game, nick: &str, _arguments: &[&str]) -> Result<Outgoing> {
+    let Game {
+        settings,
+        state,
+        log,
+        ..
+    } = game;
+
+    let dealer = state.dealers.get_dealer_available_mut(nick)?;
+
+    let event = dealer.customs_hold.as_ref().map(|hold| GameEvent::Bribed {
+        location: hold.location.name.clone(),
+        bribe: hold.bribe.clone(),
+    });
+    let lines = dealer.bribe()?;
+
+    if let Some(event) = event {
+        log.record(settings.current_day, Some(nick), event);
+    }
+
+    Ok(Outgoing::to_sender(lines))
+}
+
+fn show_price_history(game: &mut Game, nick: &str, arguments: &[&str]) -> Result<Outgoing> {
+    let view = game.view(nick);
+    let Game {
+        state, game_data, ..
+    } = game;
+
+    let location = match arguments.len() {
+        0 => return Err(Error::InvalidArguments),
+        1 => state.dealers.get_dealer(nick)?.location.clone(),
+        _ => game_data
+            .locations
+            .get_matching(&arguments[1..].join(""))?
+            .clone(),
+    };
+
+    let drug = game_data.drugs.get_matching(arguments[0])?;
+    let loc_data = state.locations.get(&location).unwrap();
+
+    Ok(Outgoing::to_source(render_price_history(
+        &view,
+        &location,
+        drug,
+        loc_data.price_history.get(drug),
+    )))
+}
+
+fn save(game: &mut Game, nick: &str, _arguments: &[&str]) -> Result<Outgoing> {
+    let Game {
+        settings,
+        state,
+        log,
+        ..
+    } = game;
+
+    settings.check_admin(nick)?;
+
+    SaveData::new(settings, state, log.position()).write(&settings.save_path)?;
+    log.record(settings.current_day, Some(nick), GameEvent::GameSaved);
+    Ok(Outgoing::to_sender(["game saved"]))
+}
+
+fn rollback(game: &mut Game, nick: &str, arguments: &[&str]) -> Result<Outgoing> {
+    let Game {
+        settings,
+        state,
+        game_data,
+        rng,
+        log,
+        ..
+    } = game;
+
+    settings.check_admin(nick)?;
+
+    let (day, confirmed) = match arguments.len() {
+        1 => (arguments[0], false),
+        2 if arguments[1] == "confirm" => (arguments[0], true),
+        _ => return Err(Error::InvalidArguments),
+    };
+    let day = NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .map_err(|_| Error::InvalidDate(day.to_owned()))?;
+
+    if day > settings.current_day {
+        return Err(Error::RollbackInFuture);
+    }
+
+    let snapshot = load_snapshot(settings, day)?;
+    let day = day.format("%Y-%m-%d").to_string();
+
+    if !confirmed {
+        return Ok(Outgoing::to_sender([Msg::new()
+            .color(Color::Red)
+            .text(format!(
+                "this puts everyone back to the start of {} and undoes everything since. ",
+                day
+            ))
+            .reset()
+            .text(format!("`rollback {} confirm` to go ahead", day))
+            .to_string()]));
+    }
+
+    let kept = keep_rolled_back(settings, &SaveData::new(settings, state, log.position()))?;
+
+    // the day keeps its schedule, only the game goes back
+    let timer = settings.timer;
+    let resumed_from = snapshot.log_position;
+    let mut restored = GameState::new(&game_data.locations);
+    snapshot.restore(game_data, settings, &mut restored)?;
+    settings.timer = timer;
+
+    // the snapshot already holds the day's markets, they're taken as is
+    rng.0 = DrugWarsRng::for_startup(settings.seed, settings.current_day).0;
+    *state = restored;
+
+    log.record(
+        settings.current_day,
+        Some(nick),
+        GameEvent::RolledBack { resumed_from },
+    );
+    SaveData::new(settings, state, log.position()).write(&settings.save_path)?;
+
+    Ok(Outgoing::to_channel([
+        Msg::new()
+            .color(Color::Red)
+            .text(format!(
+                "{} rolled the game back to the start of {}. everything since is undone",
+                nick, day
+            ))
+            .to_string(),
+        format!("the game as it was is kept in {}", kept.display()),
+    ]))
+}
+
+fn show_events(game: &mut Game, nick: &str, arguments: &[&str]) -> Result<Outgoing> {
+    let view = game.view(nick);
+    let Game { settings, log, .. } = game;
+
+    settings.check_admin(nick)?;
+
+    let (dealer_nick, count) = match arguments.len() {
+        1 => (arguments[0], 10),
+        2 => (
+            arguments[0],
+            arguments[1]
+                .parse::<usize>()
+                .map_err(|_| Error::InvalidNumber(arguments[1].to_owned()))?,
+        ),
+        _ => return Err(Error::InvalidArguments),
+    };
+
+    Ok(Outgoing::to_source(render_events(
+        &view,
+        dealer_nick,
+        &log.recent(dealer_nick, count)?,
+    )))
+}
+
+fn set_preference(game: &mut Game, nick: &str, arguments: &[&str]) -> Result<Outgoing> {
+    let Game {
+        settings,
+        state,
+        log,
+        ..
+    } = game;
+
+    let dealer = state.dealers.get_dealer_mut(nick)?;
+
+    match arguments {
+        [] => Ok(Outgoing::to_sender(
+            dealer
+                .preferences
+                .describe()
+                .into_iter()
+                .map(|(name, value)| format!("{}: {}", name, value)),
+        )),
+        [name, value] => {
+            let name = dealer.preferences.set(name, value)?;
+
+            log.record(
+                settings.current_day,
+                Some(nick),
+                GameEvent::PreferenceSet {
+                    name: name.to_owned(),
+                    value: value.to_lowercase(),
+                },
+            );
+
+            Ok(Outgoing::to_sender([format!("{} set to {}", name, value)]))
+        }
+        _ => Err(Error::InvalidArguments),
+    }
+}
+
+fn show_time(game: &mut Game, _nick: &str, _arguments: &[&str]) -> Result<Outgoing> {
+    let Game { settings, .. } = game;
+
+    Ok(Outgoing::to_sender([get_date_and_time(settings)]))
+}
+
+fn buy<E: Element>(game: &mut Game, nick: &str, arguments: &[&str]) -> Result<Outgoing> {
+    let Game {
+        settings,
+        state,
+        game_data,
+        log,
+        ..
+    } = game;
+
+    let (name, amount, max_price) = parse_trade_arguments(arguments)?;
+    let (dealer, loc_data) = state.dealer_location_mut(nick)?;
+
+    let elem = game_data.get_matching::<E>(name)?;
+    let market_elem = loc_data.get_market_element_mut(&elem)?;
+    let amount = dealer.buy_amount::<E>(&amount, market_elem, settings.day_duration)?;
+
+    let money = dealer.money.clone();
+    let lines = dealer.buy(&elem, amount, max_price, market_elem, settings.day_duration)?;
+
+    log.record(
+        settings.current_day,
+        Some(nick),
+        GameEvent::Bought {
+            kind: ElementKind::of::<E>(),
+            element: elem.name().to_owned(),
+            amount,
+            total: money - &dealer.money,
+            location: dealer.location.name.clone(),
+        },
+    );
+
+    Ok(Outgoing::to_sender(lines))
+}
+
+fn sell<E: Element>(game: &mut Game, nick: &str, arguments: &[&str]) -> Result<Outgoing> {
+    let Game {
+        settings,
+        state,
+        game_data,
+        log,
+        ..
+    } = game;
+
+    let (name, amount, min_price) = parse_trade_arguments(arguments)?;
+    let (dealer, loc_data) = state.dealer_location_mut(nick)?;
+
+    let elem = game_data.get_matching::<E>(name)?;
+    let market_elem = loc_data.get_market_element_mut(&elem)?;
+    let amount = dealer.sell_amount(&elem, &amount, market_elem, settings.day_duration)?;
+
+    let money = dealer.money.clone();
+    let lines = dealer.sell(&elem, amount, min_price, market_elem, settings.day_duration)?;
+
+    log.record(
+        settings.current_day,
+        Some(nick),
+        GameEvent::Sold {
+            kind: ElementKind::of::<E>(),
+            element: elem.name().to_owned(),
+            amount,
+            total: &dealer.money - &money,
+            location: dealer.location.name.clone(),
+        },
+    );
+
+    Ok(Outgoing::to_sender(lines))
+}
+
+fn check_trade_price<E: Element, const BUY: bool>(
+    game: &mut Game,
+    nick: &str,
+    arguments: &[&str],
+) -> Result<Outgoing> {
+    let Game {
+        settings,
+        state,
+        game_data,
+        ..
+    } = game;
+
+    if arguments.len() != 2 {
+        return Err(Error::InvalidArguments);
+    }
+
+    let (name, amount, _) = parse_trade_arguments(&[arguments[0], arguments[1]])?;
+    let (dealer, loc_data) = state.dealer_location(nick)?;
+
+    let elem = game_data.get_matching::<E>(name)?;
+    let market_elem = loc_data.get_market_element(&elem)?;
+
+    let amount = match BUY {
+        true => dealer.buy_amount::<E>(&amount, market_elem, settings.day_duration)?,
+        false => dealer.sell_amount(&elem, &amount, market_elem, settings.day_duration)?,
+    };
+
+    let quote = match BUY {
+        true => market_elem.buy_quote(amount, settings.day_duration),
+        false => market_elem.sell_quote(amount, settings.day_duration),
+    };
+
+    Ok(Outgoing::to_sender([Msg::new()
+        .text(match BUY {
+            true => "buying ",
+            false => "selling ",
+        })
+        .text(amount.pretty_amount())
+        .text(" ")
+        .color(Color::Yellow)
+        .text(elem.name())
+        .reset()
+        .text(" would get you an average price of ")
+        .color(Color::Green)
+        .text(quote.unit_price.pretty_money())
+        .reset()
+        .text(" for a total of ")
+        .color(Color::Green)
+        .text(quote.total.pretty_money())
+        .reset()]))
+}
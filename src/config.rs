@@ -1,44 +1,143 @@
-use std::{path::Path, str::FromStr, sync::Arc, time::SystemTime};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+    time::SystemTime,
+};
 
 use chrono::NaiveDate;
-use num_bigint::ToBigInt;
 use serde::Deserialize;
-use serde_yaml::{Mapping, Sequence};
+use serde_yaml::{Mapping, Sequence, Value};
 
-use crate::resources::*;
+use crate::{
+    customs::DEFAULT_CUSTOMS,
+    error::{Error, Result},
+    frontend::Route,
+    location_data::{Decay, PriceTrend},
+    money::Money,
+    resources::*,
+    scheduler::CatchUp,
+    utils::normalize_name,
+};
 
 #[derive(Deserialize)]
 pub struct DrugWarsConfig {
     pub settings: Mapping,
     pub locations: Sequence,
+    #[serde(default)]
+    pub drug_defaults: Mapping,
     pub drugs: Sequence,
     pub items: Mapping,
     pub messages: Mapping,
+    #[serde(default)]
+    pub events: Sequence,
+    #[serde(default)]
+    pub travel_modes: Sequence,
+    #[serde(default)]
+    pub routes: Mapping,
 }
 
 pub struct Settings {
     pub day_duration: u32,
     pub current_day: NaiveDate,
+    /// When the current day started.
     pub timer: SystemTime,
     pub save_path: String,
+    /// Where every game action gets appended, one JSON object per line.
+    pub event_log_path: String,
+    /// Where the game is kept at the start of every day, for rollbacks.
+    pub snapshot_dir: String,
     pub config_path: String,
     pub width: usize,
+    /// Where tables and boxes go for dealers who didn't pick.
+    pub bulky_output: Route,
+    /// Broadcast a digest of the day at rollover.
+    pub daily_summary: bool,
+    /// How many rows each section of the digest gets.
+    pub daily_summary_length: usize,
+    pub catch_up: CatchUp,
+    /// Seeds every day's random draws, so the event log can be replayed.
+    pub seed: u64,
+    /// Nicks allowed to run the admin commands.
+    pub admins: Vec<String>,
+    /// Where the read-only JSON API listens, unset when it's disabled.
+    pub api_address: Option<SocketAddr>,
 }
 
-pub fn get_game_data_from_config(
-    drugwars_config: &DrugWarsConfig,
-) -> GameData {
+fn get_drug_profile(drug: &Mapping, defaults: &DrugProfile) -> DrugProfile {
+    let get = |key: &str, default: f64| {
+        drug.get(key)
+            .and_then(|value| value.as_f64())
+            .unwrap_or(default)
+    };
+
+    // below 1 a spike would push the price the wrong way
+    let get_spike = |key: &str, default: f64| {
+        let spike = get(key, default);
+
+        if spike < 1. {
+            let name = drug.get("name").and_then(|value| value.as_str());
+            log::warn!(
+                "{} {}: {} is below 1, using 1",
+                name.unwrap_or("drug_defaults"),
+                key,
+                spike
+            );
+        }
+
+        spike.max(1.)
+    };
+
+    DrugProfile {
+        availability: get("availability", defaults.availability).clamp(0., 1.),
+        volatility: get("volatility", defaults.volatility).clamp(0., 1.),
+        spike_up: get_spike("spike_up", defaults.spike_up),
+        spike_down: get_spike("spike_down", defaults.spike_down),
+        rumor_frequency: get("rumor_frequency", defaults.rumor_frequency).clamp(0., 1.),
+    }
+}
+
+fn get_aliases(aliases: Option<&Value>) -> Vec<String> {
+    aliases
+        .and_then(|value| value.as_sequence())
+        .into_iter()
+        .flatten()
+        .filter_map(|alias| alias.as_str())
+        .map(|alias| alias.to_owned())
+        .collect()
+}
+
+fn get_only_in(item: &Mapping, locations: &Locations) -> Option<Vec<Arc<Location>>> {
+    item.get("only_in")
+        .and_then(|value| value.as_sequence())
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(|name| name.as_str())
+                .filter_map(|name| locations.get_by_name(name).ok())
+                .cloned()
+                .collect()
+        })
+}
+
+pub fn get_game_data_from_config(drugwars_config: &DrugWarsConfig) -> GameData {
     let mut drugs = Drugs::default();
     let mut locations = Locations::default();
     let mut items = Items::default();
     let mut messages = Messages::default();
 
+    let drug_defaults = get_drug_profile(&drugwars_config.drug_defaults, &DrugProfile::default());
+
     for drug in &drugwars_config.drugs {
         let name = drug.as_mapping().unwrap()["name"].as_str().unwrap();
-        let price = drug.as_mapping().unwrap()["price"].as_f64().unwrap() * 10000.;
+        let price = drug.as_mapping().unwrap()["price"].as_f64().unwrap();
         drugs.push(Arc::new(Drug {
             name: name.to_owned(),
-            nominal_price: price.to_bigint().unwrap(),
+            nominal_price: Money::from_dollars(price),
+            profile: get_drug_profile(drug.as_mapping().unwrap(), &drug_defaults),
+            aliases: get_aliases(drug.get("aliases")),
         }));
     }
 
@@ -55,9 +154,44 @@ pub fn get_game_data_from_config(
             .as_f64()
             .unwrap() as f32;
 
+        let price_multiplier = location["price_multiplier"].as_f64().unwrap_or(1.);
+
+        let mut drug_overrides = HashMap::default();
+
+        for drug_override in location["drugs"].as_sequence().into_iter().flatten() {
+            let Ok(drug) = drugs.get_by_name(drug_override["name"].as_str().unwrap_or_default())
+            else {
+                continue;
+            };
+
+            drug_overrides.insert(
+                drug.clone(),
+                DrugOverride {
+                    banned: drug_override["banned"].as_bool().unwrap_or(false),
+                    price_multiplier: drug_override["price_multiplier"].as_f64().unwrap_or(1.),
+                    availability: drug_override["availability"]
+                        .as_f64()
+                        .map(|availability| availability.clamp(0., 1.)),
+                },
+            );
+        }
+
+        let region = location["region"].as_str().unwrap_or_default();
+        let coastal = location["coastal"].as_bool().unwrap_or(false);
+        let customs = location["customs"]
+            .as_f64()
+            .unwrap_or(DEFAULT_CUSTOMS)
+            .clamp(0., 1.);
+
         locations.push(Arc::new(Location {
             name: name.to_owned(),
             position: Position { lat, long },
+            region: region.to_owned(),
+            coastal,
+            customs,
+            price_multiplier,
+            drug_overrides,
+            aliases: get_aliases(location.get("aliases")),
         }));
     }
 
@@ -70,7 +204,7 @@ pub fn get_game_data_from_config(
 
     for weapon in weapons {
         let name = weapon["name"].as_str().unwrap();
-        let price = weapon["price"].as_f64().unwrap() * 10000.;
+        let price = weapon["price"].as_f64().unwrap();
         let damage = weapon["damage"].as_f64().unwrap() as f32;
 
         let mut ammo = None;
@@ -81,8 +215,10 @@ pub fn get_game_data_from_config(
 
         items.push(Arc::new(Item {
             name: name.to_owned(),
-            nominal_price: price.to_bigint().unwrap(),
+            nominal_price: Money::from_dollars(price),
             kind: ItemKind::Weapon(Weapon { ammo, damage }),
+            only_in: get_only_in(weapon, &locations),
+            aliases: get_aliases(weapon.get("aliases")),
         }));
     }
 
@@ -95,12 +231,14 @@ pub fn get_game_data_from_config(
 
     for ammo in ammos {
         let name = ammo["name"].as_str().unwrap();
-        let price = ammo["price"].as_f64().unwrap() * 10000.;
+        let price = ammo["price"].as_f64().unwrap();
 
         items.push(Arc::new(Item {
             name: name.to_owned(),
-            nominal_price: price.to_bigint().unwrap(),
+            nominal_price: Money::from_dollars(price),
             kind: ItemKind::Ammo,
+            only_in: get_only_in(ammo, &locations),
+            aliases: get_aliases(ammo.get("aliases")),
         }));
     }
 
@@ -113,13 +251,15 @@ pub fn get_game_data_from_config(
 
     for armor in armors {
         let name = armor["name"].as_str().unwrap();
-        let price = armor["price"].as_f64().unwrap() * 10000.;
+        let price = armor["price"].as_f64().unwrap();
         let block = armor["block"].as_f64().unwrap() as f32;
 
         items.push(Arc::new(Item {
             name: name.to_owned(),
-            nominal_price: price.to_bigint().unwrap(),
+            nominal_price: Money::from_dollars(price),
             kind: ItemKind::Armor(Armor { block }),
+            only_in: get_only_in(armor, &locations),
+            aliases: get_aliases(armor.get("aliases")),
         }));
     }
 
@@ -134,11 +274,79 @@ pub fn get_game_data_from_config(
         messages.insert(key.to_owned(), val);
     }
 
+    let mut events = vec![];
+
+    for event in &drugwars_config.events {
+        let trend = match event["trend"].as_str() {
+            Some("down") => PriceTrend::Down,
+            _ => PriceTrend::Up,
+        };
+
+        let decay = match event["decay"].as_str() {
+            Some("linear") => Decay::Linear,
+            Some("exponential") => Decay::Exponential,
+            _ => Decay::Constant,
+        };
+
+        let event_drugs = event["drugs"]
+            .as_sequence()
+            .into_iter()
+            .flatten()
+            .filter_map(|name| name.as_str())
+            .filter_map(|name| drugs.get_by_name(name).ok())
+            .cloned()
+            .collect();
+
+        events.push(Arc::new(MarketEvent {
+            name: event["name"].as_str().unwrap().to_owned(),
+            drugs: event_drugs,
+            trend,
+            chance: event["chance"].as_f64().unwrap().clamp(0., 1.),
+            duration: event["duration"].as_u64().unwrap_or(1).max(1) as u32,
+            decay,
+            spread: event["spread"].as_f64().unwrap_or(0.) as f32,
+        }));
+    }
+
+    let mut travel_modes = TravelModes::default();
+
+    for mode in &drugwars_config.travel_modes {
+        travel_modes.push(Arc::new(TravelMode {
+            name: mode["name"].as_str().unwrap().to_owned(),
+            price_multiplier: mode["price"].as_f64().unwrap_or(1.),
+            speed: mode["speed"].as_f64().unwrap() as f32,
+            risk: mode["risk"].as_f64().unwrap_or(0.).clamp(0., 1.),
+            same_region: mode["same_region"].as_bool().unwrap_or(false),
+            coastal: mode["coastal"].as_bool().unwrap_or(false),
+        }));
+    }
+
+    if travel_modes.is_empty() {
+        travel_modes.push(Arc::new(TravelMode::plane(5000.)));
+    }
+
+    let mut routes = Routes::default();
+
+    for (from, destinations) in &drugwars_config.routes {
+        let Ok(from) = locations.get_by_name(from.as_str().unwrap_or_default()) else {
+            continue;
+        };
+
+        for to in destinations.as_sequence().into_iter().flatten() {
+            if let Ok(to) = locations.get_by_name(to.as_str().unwrap_or_default()) {
+                routes.connect(from, to);
+            }
+        }
+    }
+
     GameData {
         drugs,
         items,
         locations,
-        messages
+        messages,
+        events,
+        travel_modes,
+        routes,
     }
 }
 
@@ -146,17 +354,394 @@ pub fn get_settings_from_config(
     drugwars_config: &DrugWarsConfig,
     config_path: impl AsRef<Path>,
 ) -> Settings {
+    if drugwars_config.settings.get("flight_speed").is_some() {
+        log::warn!("{}", FLIGHT_SPEED_MOVED);
+    }
+
     let day_duration = drugwars_config.settings["day_duration"].as_u64().unwrap() as u32;
     let current_day_str = drugwars_config.settings["start_day"].as_str().unwrap();
     let save_path = drugwars_config.settings["save_path"].as_str().unwrap();
+    let event_log_path = drugwars_config
+        .settings
+        .get("event_log_path")
+        .and_then(|value| value.as_str())
+        .unwrap_or("events.jsonl");
+    let snapshot_dir = drugwars_config
+        .settings
+        .get("snapshot_dir")
+        .and_then(|value| value.as_str())
+        .unwrap_or("snapshots");
     let width = drugwars_config.settings["width"].as_u64().unwrap();
+    let bulky_output = drugwars_config
+        .settings
+        .get("bulky_output")
+        .and_then(|value| value.as_str())
+        .map_or(Route::Notice, |route| route.parse().unwrap());
+    let daily_summary = drugwars_config
+        .settings
+        .get("daily_summary")
+        .and_then(|value| value.as_bool())
+        .unwrap_or(true);
+    let daily_summary_length = drugwars_config
+        .settings
+        .get("daily_summary_length")
+        .and_then(|value| value.as_u64())
+        .unwrap_or(3);
+    let catch_up = match drugwars_config
+        .settings
+        .get("catch_up_days")
+        .and_then(|value| value.as_u64())
+    {
+        Some(limit) => CatchUp::Limit(limit as u32),
+        None => CatchUp::All,
+    };
+    // a saved game keeps the seed it started with
+    let seed = drugwars_config
+        .settings
+        .get("seed")
+        .and_then(|value| value.as_u64())
+        .unwrap_or_else(rand::random);
+    let api_address = drugwars_config
+        .settings
+        .get("api_enabled")
+        .and_then(|value| value.as_bool())
+        .unwrap_or(false)
+        .then(|| {
+            let address = drugwars_config
+                .settings
+                .get("api_address")
+                .and_then(|value| value.as_str())
+                .unwrap_or("127.0.0.1:8080");
+
+            // a typo only costs the API, not the game
+            address
+                .parse()
+                .map_err(|err| log::error!("API disabled, bad api_address {}: {}", address, err))
+                .ok()
+        })
+        .flatten();
+
+    let admins = drugwars_config
+        .settings
+        .get("admins")
+        .and_then(|value| value.as_sequence())
+        .into_iter()
+        .flatten()
+        .filter_map(|nick| nick.as_str())
+        .map(str::to_owned)
+        .collect();
 
     Settings {
         day_duration,
         current_day: NaiveDate::from_str(current_day_str).unwrap(),
         timer: SystemTime::now(),
         save_path: save_path.to_owned(),
+        event_log_path: event_log_path.to_owned(),
+        snapshot_dir: snapshot_dir.to_owned(),
         config_path: config_path.as_ref().to_str().unwrap().to_string(),
         width: width as usize,
+        bulky_output,
+        daily_summary,
+        daily_summary_length: daily_summary_length as usize,
+        catch_up,
+        seed,
+        admins,
+        api_address,
+    }
+}
+
+impl Settings {
+    pub fn check_admin(&self, nick: &str) -> Result<()> {
+        match self
+            .admins
+            .iter()
+            .any(|admin| admin.eq_ignore_ascii_case(nick))
+        {
+            true => Ok(()),
+            false => Err(Error::NotAdmin),
+        }
+    }
+}
+
+/// `flight_speed` was replaced by a speed per travel mode.
+const FLIGHT_SPEED_MOVED: &str =
+    "flight_speed: no longer used, set the plane's speed under travel_modes instead";
+
+/// Looks for mistakes the game would silently ignore, used by `--check-config`.
+pub fn check_config(drugwars_config: &DrugWarsConfig) -> Vec<String> {
+    let mut problems = vec![];
+
+    let name_of = |value: &Value| value["name"].as_str().unwrap_or_default().to_owned();
+
+    let drug_names = drugwars_config
+        .drugs
+        .iter()
+        .map(name_of)
+        .collect::<Vec<_>>();
+    let location_names = drugwars_config
+        .locations
+        .iter()
+        .map(name_of)
+        .collect::<Vec<_>>();
+
+    let check_probability = |problems: &mut Vec<String>, what: &str, value: &Value| {
+        if let Some(probability) = value.as_f64() {
+            if !(0. ..=1.).contains(&probability) {
+                problems.push(format!("{}: {} is not between 0 and 1", what, probability));
+            }
+        }
+    };
+
+    let check_positive = |problems: &mut Vec<String>, what: &str, value: &Value| {
+        if let Some(number) = value.as_f64() {
+            if number <= 0. {
+                problems.push(format!("{}: {} should be above 0", what, number));
+            }
+        }
+    };
+
+    if let Some(address) = drugwars_config
+        .settings
+        .get("api_address")
+        .and_then(|value| value.as_str())
+    {
+        if address.parse::<SocketAddr>().is_err() {
+            problems.push(format!(
+                "api_address: {} isn't an address like 127.0.0.1:8080",
+                address
+            ));
+        }
     }
+
+    if let Some(route) = drugwars_config
+        .settings
+        .get("bulky_output")
+        .and_then(|value| value.as_str())
+    {
+        if let Err(err) = route.parse::<Route>() {
+            problems.push(format!("bulky_output: {}", err));
+        }
+    }
+
+    if drugwars_config.settings.get("flight_speed").is_some() {
+        problems.push(FLIGHT_SPEED_MOVED.to_owned());
+    }
+
+    check_positive(
+        &mut problems,
+        "catch_up_days",
+        drugwars_config
+            .settings
+            .get("catch_up_days")
+            .unwrap_or(&Value::Null),
+    );
+
+    for drug in drugwars_config
+        .drugs
+        .iter()
+        .chain([&Value::Mapping(drugwars_config.drug_defaults.clone())])
+    {
+        let drug_name = match drug["name"].as_str() {
+            Some(name) => name.to_owned(),
+            None => "drug_defaults".to_owned(),
+        };
+
+        for key in ["availability", "volatility", "rumor_frequency"] {
+            check_probability(&mut problems, &format!("{} {}", drug_name, key), &drug[key]);
+        }
+
+        for key in ["spike_up", "spike_down"] {
+            if let Some(spike) = drug[key].as_f64() {
+                if spike < 1. {
+                    problems.push(format!(
+                        "{} {}: {} should be at least 1",
+                        drug_name, key, spike
+                    ));
+                }
+            }
+        }
+    }
+
+    for location in &drugwars_config.locations {
+        let location_name = name_of(location);
+
+        check_positive(
+            &mut problems,
+            &format!("{} price_multiplier", location_name),
+            &location["price_multiplier"],
+        );
+        check_probability(
+            &mut problems,
+            &format!("{} customs", location_name),
+            &location["customs"],
+        );
+
+        for drug_override in location["drugs"].as_sequence().into_iter().flatten() {
+            let drug_name = name_of(drug_override);
+
+            if !drug_names.contains(&drug_name) {
+                problems.push(format!("{}: unknown drug \"{}\"", location_name, drug_name));
+            }
+
+            check_positive(
+                &mut problems,
+                &format!("{} {} price_multiplier", location_name, drug_name),
+                &drug_override["price_multiplier"],
+            );
+            check_probability(
+                &mut problems,
+                &format!("{} {} availability", location_name, drug_name),
+                &drug_override["availability"],
+            );
+        }
+    }
+
+    for event in &drugwars_config.events {
+        let event_name = name_of(event);
+
+        check_probability(
+            &mut problems,
+            &format!("{} chance", event_name),
+            &event["chance"],
+        );
+
+        for drug_name in event["drugs"].as_sequence().into_iter().flatten() {
+            let drug_name = drug_name.as_str().unwrap_or_default().to_owned();
+
+            if !drug_names.contains(&drug_name) {
+                problems.push(format!("{}: unknown drug \"{}\"", event_name, drug_name));
+            }
+        }
+
+        if let Some(decay) = event["decay"].as_str() {
+            if !["constant", "linear", "exponential"].contains(&decay) {
+                problems.push(format!("{}: unknown decay \"{}\"", event_name, decay));
+            }
+        }
+
+        check_positive(
+            &mut problems,
+            &format!("{} spread", event_name),
+            &event["spread"],
+        );
+    }
+
+    for mode in &drugwars_config.travel_modes {
+        let mode_name = name_of(mode);
+
+        check_probability(&mut problems, &format!("{} risk", mode_name), &mode["risk"]);
+        check_positive(
+            &mut problems,
+            &format!("{} speed", mode_name),
+            &mode["speed"],
+        );
+        check_positive(
+            &mut problems,
+            &format!("{} price", mode_name),
+            &mode["price"],
+        );
+    }
+
+    for kind in ["weapons", "ammos", "armors"] {
+        for item in drugwars_config.items[kind]
+            .as_sequence()
+            .into_iter()
+            .flatten()
+        {
+            for location_name in item["only_in"].as_sequence().into_iter().flatten() {
+                let location_name = location_name.as_str().unwrap_or_default().to_owned();
+
+                if !location_names.contains(&location_name) {
+                    problems.push(format!(
+                        "{}: unknown location \"{}\"",
+                        name_of(item),
+                        location_name
+                    ));
+                }
+            }
+        }
+    }
+
+    let items = ["weapons", "ammos", "armors"]
+        .into_iter()
+        .flat_map(|kind| {
+            drugwars_config.items[kind]
+                .as_sequence()
+                .into_iter()
+                .flatten()
+        })
+        .collect::<Vec<_>>();
+
+    for elements in [
+        drugwars_config.drugs.iter().collect::<Vec<_>>(),
+        drugwars_config.locations.iter().collect(),
+        items,
+    ] {
+        let mut seen: HashMap<String, String> = HashMap::default();
+
+        for element in elements {
+            let element_name = name_of(element);
+
+            for alias in element["aliases"].as_sequence().into_iter().flatten() {
+                let alias = alias.as_str().unwrap_or_default();
+
+                if let Some(other) = seen.insert(normalize_name(alias), element_name.clone()) {
+                    problems.push(format!(
+                        "alias \"{}\" is used by both {} and {}",
+                        alias, other, element_name
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut connections: HashMap<&str, Vec<&str>> = HashMap::default();
+
+    for (from, destinations) in &drugwars_config.routes {
+        let from = from.as_str().unwrap_or_default();
+
+        if !location_names.iter().any(|name| name == from) {
+            problems.push(format!("routes: unknown location \"{}\"", from));
+            continue;
+        }
+
+        for to in destinations
+            .as_sequence()
+            .into_iter()
+            .flatten()
+            .map(|to| to.as_str().unwrap_or_default())
+        {
+            if !location_names.iter().any(|name| name == to) {
+                problems.push(format!("routes: unknown location \"{}\"", to));
+                continue;
+            }
+
+            connections.entry(from).or_default().push(to);
+            connections.entry(to).or_default().push(from);
+        }
+    }
+
+    if let (false, Some(first)) = (connections.is_empty(), location_names.first()) {
+        let mut reached = HashSet::from([first.as_str()]);
+        let mut queue = vec![first.as_str()];
+
+        while let Some(location) = queue.pop() {
+            for next in connections.get(location).into_iter().flatten() {
+                if reached.insert(next) {
+                    queue.push(next);
+                }
+            }
+        }
+
+        for location_name in &location_names {
+            if !reached.contains(location_name.as_str()) {
+                problems.push(format!(
+                    "routes: {} can't be reached from {}",
+                    location_name, first
+                ));
+            }
+        }
+    }
+
+    problems
 }
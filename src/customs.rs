@@ -0,0 +1,85 @@
+use std::{collections::HashMap, sync::Arc};
+
+use itertools::Itertools;
+use rand::Rng;
+
+use crate::{
+    element::OwnedElement,
+    money::Money,
+    resources::{Drug, Location},
+    utils::PrettyAmount,
+};
+
+/// Drugs travelling with a dealer instead of sitting in a local stash.
+pub type Cargo = HashMap<Arc<Drug>, OwnedElement>;
+
+/// Used for locations without a `customs` chance.
+pub const DEFAULT_CUSTOMS: f64 = 0.1;
+/// Carrying this many units makes an inspection as likely as the location's
+/// `customs` chance, more makes it likelier.
+pub const CUSTOMS_UNITS: f64 = 10.;
+/// Share of the cargo's local value asked as a fine.
+pub const FINE_RATE: f64 = 0.5;
+/// Share of the cargo's local value asked as a bribe.
+pub const BRIBE_RATE: f64 = 0.3;
+/// Inspections are this much likelier when carrying drugs banned at the
+/// destination.
+pub const BANNED_MULTIPLIER: f64 = 2.;
+
+pub enum CustomsOutcome {
+    Cleared,
+    Seized,
+    Fined(Money),
+    Bribe(Money),
+}
+
+/// Cargo kept by customs until the dealer pays the bribe, seized at the next
+/// day otherwise.
+pub struct CustomsHold {
+    pub location: Arc<Location>,
+    pub cargo: Cargo,
+    pub bribe: Money,
+}
+
+pub fn cargo_amount(cargo: &Cargo) -> usize {
+    cargo.values().map(|owned| owned.amount).sum()
+}
+
+pub fn cargo_value(cargo: &Cargo, location: &Location) -> Money {
+    cargo
+        .iter()
+        .map(|(drug, owned)| location.drug_price(drug) * owned.amount)
+        .sum()
+}
+
+pub fn describe_cargo(cargo: &Cargo) -> String {
+    cargo
+        .iter()
+        .map(|(drug, owned)| format!("{} {}", owned.amount.pretty_amount(), drug.name))
+        .join(", ")
+}
+
+pub fn inspection_chance(cargo: &Cargo, location: &Location) -> f64 {
+    let amount = cargo_amount(cargo) as f64;
+    let mut chance = 1. - (1. - location.customs).powf(amount / CUSTOMS_UNITS);
+
+    if cargo.keys().any(|drug| !location.sells_drug(drug)) {
+        chance *= BANNED_MULTIPLIER;
+    }
+
+    chance.clamp(0., 1.)
+}
+
+pub fn inspect(cargo: &Cargo, location: &Location, rng: &mut impl Rng) -> CustomsOutcome {
+    if cargo.is_empty() || !rng.gen_bool(inspection_chance(cargo, location)) {
+        return CustomsOutcome::Cleared;
+    }
+
+    let value = cargo_value(cargo, location);
+
+    match rng.gen_range(0..3) {
+        0 => CustomsOutcome::Seized,
+        1 => CustomsOutcome::Fined(value.scale(FINE_RATE)),
+        _ => CustomsOutcome::Bribe(value.scale(BRIBE_RATE)),
+    }
+}
@@ -1,22 +1,29 @@
 use std::{
-    collections::HashMap,
-    ops::Deref,
-    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    any::{Any, TypeId},
+    collections::{HashMap, VecDeque},
+    ops::{Deref, DerefMut},
+    sync::Arc,
 };
 
-use chrono::NaiveDate;
+use chrono::{Duration, NaiveDate};
 use ircie::format::{Color, Msg};
-use num_bigint::BigInt;
+use itertools::Itertools;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    element::OwnedElement,
+    customs::{cargo_amount, describe_cargo, inspect, Cargo, CustomsHold, CustomsOutcome},
+    element::{Element, MarketElement, OwnedElement},
     error::{Error, Result},
+    event_log::{log_cargo, GameEvent},
     location_data::SingleLocationData,
-    resources::{Drug, Flights, Item, Location},
-    utils::{get_flight_price, PrettyMoney},
+    money::Money,
+    preferences::Preferences,
+    resources::{Drug, Flight, Flights, Item, Location, Locations, Routes, TravelMode},
+    utils::{get_route_days, get_route_price, max_affordable, Amount, PrettyAmount, PrettyMoney},
 };
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DealerStatus {
     Available,
     Flying,
@@ -42,24 +49,29 @@ impl DealerStatus {
 }
 
 #[derive(Default)]
-pub struct Dealers(pub HashMap<String, Arc<RwLock<Dealer>>>);
+pub struct Dealers(pub HashMap<String, Dealer>);
 impl Deref for Dealers {
-    type Target = HashMap<String, Arc<RwLock<Dealer>>>;
+    type Target = HashMap<String, Dealer>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
+impl DerefMut for Dealers {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
 
 impl Dealers {
-    pub fn get_dealer(&self, nick: &str) -> Result<RwLockReadGuard<Dealer>> {
+    pub fn get_dealer(&self, nick: &str) -> Result<&Dealer> {
         match self.get(nick) {
-            Some(dealer) => Ok(dealer.read().unwrap()),
+            Some(dealer) => Ok(dealer),
             None => Err(Error::DealerNotFound(nick.to_owned())),
         }
     }
 
-    pub fn get_dealer_available(&self, nick: &str) -> Result<RwLockReadGuard<Dealer>> {
+    pub fn get_dealer_available(&self, nick: &str) -> Result<&Dealer> {
         let dealer = self.get_dealer(nick)?;
 
         if !dealer.available() {
@@ -72,14 +84,31 @@ impl Dealers {
         Ok(dealer)
     }
 
-    pub fn get_dealer_mut(&self, nick: &str) -> Result<RwLockWriteGuard<Dealer>> {
-        match self.get(nick) {
-            Some(dealer) => Ok(dealer.write().unwrap()),
+    pub fn get_dealer_mut(&mut self, nick: &str) -> Result<&mut Dealer> {
+        match self.get_mut(nick) {
+            Some(dealer) => Ok(dealer),
             None => Err(Error::DealerNotFound(nick.to_owned())),
         }
     }
 
-    pub fn get_dealer_available_mut(&self, nick: &str) -> Result<RwLockWriteGuard<Dealer>> {
+    /// The hardest dealers, ranked by laundered money.
+    pub fn leaderboard(&self, count: usize) -> Vec<(String, Money)> {
+        self.values()
+            .map(|dealer| (dealer.nick.clone(), dealer.laundered_money.clone()))
+            .sorted_by(|a, b| b.1.cmp(&a.1))
+            .take(count)
+            .collect()
+    }
+
+    pub fn died_on(&self, day: NaiveDate) -> Vec<String> {
+        self.values()
+            .filter(|dealer| dealer.status == DealerStatus::Dead(day))
+            .map(|dealer| dealer.nick.clone())
+            .sorted()
+            .collect()
+    }
+
+    pub fn get_dealer_available_mut(&mut self, nick: &str) -> Result<&mut Dealer> {
         let dealer = self.get_dealer_mut(nick)?;
 
         if !dealer.available() {
@@ -97,28 +126,322 @@ pub struct Dealer {
     pub nick: String,
     pub has_attacked: bool,
     pub health: f32,
-    pub money: BigInt,
-    pub laundered_money: BigInt,
+    pub money: Money,
+    pub laundered_money: Money,
     pub location: Arc<Location>,
     pub capacity: usize,
     pub owned_drugs: HashMap<Arc<Location>, HashMap<Arc<Drug>, OwnedElement>>,
     pub owned_items: HashMap<Arc<Location>, HashMap<Arc<Item>, OwnedElement>>,
+    /// Drugs packed to travel with the dealer on the next trip.
+    pub carry_on: Cargo,
+    pub customs_hold: Option<CustomsHold>,
     pub status: DealerStatus,
+    pub preferences: Preferences,
     //pub looters: HashSet<String>,
 }
 
 impl Dealer {
+    pub fn new(nick: &str, location: Arc<Location>, money: Money, locations: &Locations) -> Self {
+        let mut owned_drugs = HashMap::default();
+        let mut owned_items = HashMap::default();
+
+        for loc in locations.iter() {
+            owned_drugs.insert(loc.clone(), HashMap::default());
+            owned_items.insert(loc.clone(), HashMap::default());
+        }
+
+        Self {
+            nick: nick.to_owned(),
+            has_attacked: false,
+            health: 100.,
+            money,
+            laundered_money: Money::default(),
+            location,
+            capacity: 10,
+            owned_drugs,
+            owned_items,
+            carry_on: HashMap::default(),
+            customs_hold: None,
+            status: DealerStatus::Available,
+            preferences: Preferences::default(),
+        }
+    }
+
     pub fn available(&self) -> bool {
         self.status == DealerStatus::Available
     }
 
+    pub fn get_owned_local<E: Element>(&self) -> &HashMap<Arc<E>, OwnedElement> {
+        let owned: &dyn Any = if TypeId::of::<E>() == TypeId::of::<Drug>() {
+            self.owned_drugs.get(&self.location).unwrap()
+        } else {
+            self.owned_items.get(&self.location).unwrap()
+        };
+
+        owned.downcast_ref().unwrap()
+    }
+
+    pub fn get_owned_local_mut<E: Element>(&mut self) -> &mut HashMap<Arc<E>, OwnedElement> {
+        let owned: &mut dyn Any = if TypeId::of::<E>() == TypeId::of::<Drug>() {
+            self.owned_drugs.get_mut(&self.location).unwrap()
+        } else {
+            self.owned_items.get_mut(&self.location).unwrap()
+        };
+
+        owned.downcast_mut().unwrap()
+    }
+
+    pub fn get_total_owned_local<E: Element>(&self) -> usize {
+        self.get_owned_local::<E>()
+            .values()
+            .map(|owned| owned.amount)
+            .sum()
+    }
+
+    /// Units taking up room here, the carry-on counts against the drugs.
+    pub fn get_total_held_local<E: Element>(&self) -> usize {
+        let mut held = self.get_total_owned_local::<E>();
+
+        if TypeId::of::<E>() == TypeId::of::<Drug>() {
+            held += cargo_amount(&self.carry_on);
+        }
+
+        held
+    }
+
+    /// Stashes what fits of `cargo` here, the rest stays in the carry-on.
+    /// Returns how many units didn't fit.
+    pub fn stash(&mut self, cargo: Cargo) -> usize {
+        let mut room = self
+            .capacity
+            .saturating_sub(self.get_total_held_local::<Drug>());
+        let mut stashed = Cargo::default();
+        let mut kept = Cargo::default();
+
+        // by name, so replay splits the cargo the same way
+        for (drug, owned) in cargo.into_iter().sorted_by(|a, b| a.0.name.cmp(&b.0.name)) {
+            let fits = owned.amount.min(room);
+            room -= fits;
+
+            for (units, into) in [(fits, &mut stashed), (owned.amount - fits, &mut kept)] {
+                into.insert(
+                    drug.clone(),
+                    OwnedElement {
+                        amount: units,
+                        bought_at: owned.bought_at.clone(),
+                    },
+                );
+            }
+        }
+
+        let left = cargo_amount(&kept);
+        merge_owned(self.get_owned_local_mut::<Drug>(), stashed);
+        merge_owned(&mut self.carry_on, kept);
+
+        left
+    }
+
+    /// Units `amount` comes down to when buying here.
+    pub fn buy_amount<E: Element>(
+        &self,
+        amount: &Amount,
+        market: &MarketElement,
+        day_duration: u32,
+    ) -> Result<usize> {
+        let cost = |units| market.buy_quote(units, day_duration).total;
+        let room = self
+            .capacity
+            .saturating_sub(self.get_total_held_local::<E>());
+
+        amount.resolve(
+            max_affordable(room.min(market.supply), &self.money, cost),
+            cost,
+        )
+    }
+
+    /// Units `amount` comes down to when selling `elem` here.
+    pub fn sell_amount<E: Element>(
+        &self,
+        elem: &Arc<E>,
+        amount: &Amount,
+        market: &MarketElement,
+        day_duration: u32,
+    ) -> Result<usize> {
+        let owned = self
+            .get_owned_local::<E>()
+            .get(elem)
+            .map_or(0, |owned| owned.amount);
+
+        amount.resolve(owned.min(market.demand), |units| {
+            market.sell_quote(units, day_duration).total
+        })
+    }
+
+    pub fn buy<E: Element>(
+        &mut self,
+        elem: &Arc<E>,
+        amount: usize,
+        max_price: Option<Money>,
+        market: &mut MarketElement,
+        day_duration: u32,
+    ) -> Result<Vec<String>> {
+        if market.supply < amount {
+            return Err(Error::NotEnoughSupply(elem.name().to_owned()));
+        }
+
+        let room = self
+            .capacity
+            .saturating_sub(self.get_total_held_local::<E>());
+
+        if room < amount {
+            return Err(Error::NotEnoughCapacity(room));
+        }
+
+        let quote = market.buy_quote(amount, day_duration);
+
+        if let Some(max_price) = max_price {
+            if quote.unit_price > max_price {
+                return Err(Error::PriceLimitReached(quote.unit_price.pretty_money()));
+            }
+        }
+
+        if self.money < quote.total {
+            return Err(Error::NotEnoughMoney);
+        }
+
+        self.settle_buy(elem, amount, &quote.total);
+        market.apply_buy(&quote);
+
+        Ok(vec![Msg::new()
+            .text("you bought ")
+            .text(amount.pretty_amount())
+            .text(" ")
+            .color(Color::Yellow)
+            .text(elem.name())
+            .reset()
+            .text(" for ")
+            .color(Color::Green)
+            .text(quote.total.pretty_money())
+            .reset()
+            .text(" (")
+            .color(Color::Green)
+            .text(quote.unit_price.pretty_money())
+            .reset()
+            .text(" each). market price is now ")
+            .color(Color::Green)
+            .text(market.current_price(day_duration).pretty_money())
+            .reset()
+            .to_string()])
+    }
+
+    pub fn sell<E: Element>(
+        &mut self,
+        elem: &Arc<E>,
+        amount: usize,
+        min_price: Option<Money>,
+        market: &mut MarketElement,
+        day_duration: u32,
+    ) -> Result<Vec<String>> {
+        let owned_amount = self
+            .get_owned_local::<E>()
+            .get(elem)
+            .map_or(0, |owned| owned.amount);
+
+        if owned_amount < amount {
+            return Err(Error::NotEnoughElement(elem.name().to_owned()));
+        }
+
+        if market.demand < amount {
+            return Err(Error::NotEnoughDemand(elem.name().to_owned()));
+        }
+
+        let quote = market.sell_quote(amount, day_duration);
+
+        if let Some(min_price) = min_price {
+            if quote.unit_price < min_price {
+                return Err(Error::PriceLimitReached(quote.unit_price.pretty_money()));
+            }
+        }
+
+        self.settle_sell(elem, amount, &quote.total)?;
+        market.apply_sell(&quote);
+
+        Ok(vec![Msg::new()
+            .text("you sold ")
+            .text(amount.pretty_amount())
+            .text(" ")
+            .color(Color::Yellow)
+            .text(elem.name())
+            .reset()
+            .text(" for ")
+            .color(Color::Green)
+            .text(quote.total.pretty_money())
+            .reset()
+            .text(" (")
+            .color(Color::Green)
+            .text(quote.unit_price.pretty_money())
+            .reset()
+            .text(" each). market price is now ")
+            .color(Color::Green)
+            .text(market.current_price(day_duration).pretty_money())
+            .reset()
+            .to_string()])
+    }
+
+    /// Pays `total` for `amount` more `elem` in the local stash.
+    pub fn settle_buy<E: Element>(&mut self, elem: &Arc<E>, amount: usize, total: &Money) {
+        self.money -= total;
+
+        let owned = self
+            .get_owned_local_mut::<E>()
+            .entry(elem.clone())
+            .or_insert(OwnedElement {
+                amount: 0,
+                bought_at: Money::default(),
+            });
+
+        owned.bought_at =
+            (owned.bought_at.clone() * owned.amount + total) / (owned.amount + amount);
+        owned.amount += amount;
+    }
+
+    /// Gets `total` for `amount` `elem` taken from the local stash.
+    pub fn settle_sell<E: Element>(
+        &mut self,
+        elem: &Arc<E>,
+        amount: usize,
+        total: &Money,
+    ) -> Result<()> {
+        take_owned(self.get_owned_local_mut::<E>(), elem, amount)?;
+        self.money += total;
+
+        Ok(())
+    }
+
     pub fn fly_to(
         &mut self,
         flights: &mut Flights,
+        routes: &Routes,
         destination: &Arc<Location>,
+        mode: &Arc<TravelMode>,
         current_location_data: &mut SingleLocationData,
+        current_day: NaiveDate,
     ) -> Result<Vec<String>> {
-        let price = get_flight_price(&self.location, &destination);
+        let Some(mut route) = routes.find(&self.location, destination, mode) else {
+            return Err(Error::TravelModeUnavailable(
+                destination.name.clone(),
+                mode.name.clone(),
+            ));
+        };
+
+        if self.customs_hold.is_some() {
+            return Err(Error::CustomsHoldOpen);
+        }
+
+        let price = get_route_price(&self.location, &route, mode);
+        let legs = get_route_days(&self.location, &route, mode);
+        let days = legs.iter().sum::<i64>();
+        let arrival = current_day + Duration::days(days);
 
         if self.money < price {
             return Err(Error::NotEnoughMoney);
@@ -128,18 +451,308 @@ impl Dealer {
         current_location_data.people.remove(&self.nick);
         self.money -= price.clone();
 
-        flights.insert(self.nick.clone(), destination.clone());
+        // Leaving a layover keeps what was on board.
+        let mut cargo = flights
+            .remove(&self.nick)
+            .map(|flight| flight.cargo)
+            .unwrap_or_default();
+        merge_owned(&mut cargo, std::mem::take(&mut self.carry_on));
+
+        route.pop();
+
+        let layovers = route
+            .iter()
+            .zip(legs.iter().scan(current_day, |day, leg| {
+                *day += Duration::days(*leg);
+                Some(*day)
+            }))
+            .map(|(location, day)| (location.clone(), day))
+            .collect::<VecDeque<_>>();
+
+        let via = match layovers.len() {
+            0 => String::new(),
+            _ => format!(
+                " via {}",
+                layovers
+                    .iter()
+                    .map(|(location, _)| location.name.as_str())
+                    .join(", ")
+            ),
+        };
+
+        flights.insert(
+            self.nick.clone(),
+            Flight {
+                destination: destination.clone(),
+                mode: mode.clone(),
+                cargo,
+                arrival,
+                layovers,
+                layover: None,
+            },
+        );
+
+        let eta = match days {
+            1 => "tomorrow".to_owned(),
+            _ => format!("on {} ({} days)", arrival.format("%Y-%m-%d"), days),
+        };
 
         Ok(vec![Msg::new()
-            .text("you took a flight to ")
+            .text(format!("you took a {} to ", mode.name))
             .color(Color::Purple)
             .text(&destination.name)
             .reset()
+            .text(via)
             .text(" for ")
             .color(Color::Green)
             .text(price.pretty_money())
             .reset()
-            .text(". You'll arrive tomorrow")
+            .text(format!(". You'll arrive {}", eta))
+            .to_string()])
+    }
+
+    pub fn pack(&mut self, drug: &Arc<Drug>, amount: &Amount) -> Result<Vec<String>> {
+        let room = self.capacity.saturating_sub(cargo_amount(&self.carry_on));
+        let owned = self
+            .get_owned_local::<Drug>()
+            .get(drug)
+            .map_or(0, |owned| owned.amount);
+        let amount = amount.resolve(owned.min(room), |units| {
+            self.location.drug_price(drug) * units
+        })?;
+
+        if room < amount {
+            return Err(Error::NotEnoughCapacity(room));
+        }
+
+        let packed = take_owned(self.get_owned_local_mut::<Drug>(), drug, amount)?;
+        merge_owned(&mut self.carry_on, HashMap::from([(drug.clone(), packed)]));
+
+        Ok(vec![Msg::new()
+            .text("you packed ")
+            .text(amount.pretty_amount())
+            .text(" ")
+            .color(Color::Yellow)
+            .text(&drug.name)
+            .reset()
+            .text(" in your carry-on")
+            .to_string()])
+    }
+
+    pub fn unpack(&mut self, drug: &Arc<Drug>, amount: &Amount) -> Result<Vec<String>> {
+        let room = self
+            .capacity
+            .saturating_sub(self.get_total_owned_local::<Drug>());
+        let packed = self.carry_on.get(drug).map_or(0, |owned| owned.amount);
+        let amount = amount.resolve(packed.min(room), |units| {
+            self.location.drug_price(drug) * units
+        })?;
+
+        if room < amount {
+            return Err(Error::NotEnoughCapacity(room));
+        }
+
+        let unpacked = take_owned(&mut self.carry_on, drug, amount)?;
+        merge_owned(
+            self.get_owned_local_mut::<Drug>(),
+            HashMap::from([(drug.clone(), unpacked)]),
+        );
+
+        Ok(vec![Msg::new()
+            .text("you unpacked ")
+            .text(amount.pretty_amount())
+            .text(" ")
+            .color(Color::Yellow)
+            .text(&drug.name)
+            .reset()
+            .text(format!(" in {}", self.location.name))
             .to_string()])
     }
+
+    /// Takes `cargo` through customs at the dealer's location and stashes
+    /// whatever gets through. Nothing happens without cargo, and only
+    /// inspections get a message.
+    pub fn clear_customs(
+        &mut self,
+        cargo: Cargo,
+        rng: &mut impl Rng,
+    ) -> Option<(GameEvent, Option<Msg>)> {
+        if cargo.is_empty() {
+            return None;
+        }
+
+        let msg = Msg::new().text(format!("{}: ", self.nick));
+        let location = self.location.name.clone();
+        let logged = log_cargo(&cargo);
+
+        match inspect(&cargo, &self.location, rng) {
+            CustomsOutcome::Cleared => {
+                let left = self.stash(cargo);
+
+                Some((
+                    GameEvent::CustomsCleared {
+                        location,
+                        cargo: logged,
+                    },
+                    (left > 0).then(|| msg.text(kept_on_board(left))),
+                ))
+            }
+            CustomsOutcome::Seized => Some((
+                GameEvent::CustomsSeized {
+                    location,
+                    cargo: logged,
+                },
+                Some(
+                    msg.color(Color::Red)
+                        .text("customs seized your carry-on: ")
+                        .reset()
+                        .text(describe_cargo(&cargo)),
+                ),
+            )),
+            CustomsOutcome::Fined(fine) if self.money < fine => Some((
+                GameEvent::FineUnpaid {
+                    location,
+                    cargo: logged,
+                    fine: fine.clone(),
+                },
+                Some(
+                    msg.color(Color::Red)
+                        .text("customs seized your carry-on, you couldn't pay a ")
+                        .text(fine.pretty_money())
+                        .text(" fine: ")
+                        .reset()
+                        .text(describe_cargo(&cargo)),
+                ),
+            )),
+            CustomsOutcome::Fined(fine) => {
+                self.money -= &fine;
+                let left = self.stash(cargo);
+
+                let mut msg = msg
+                    .color(Color::Red)
+                    .text("customs fined you ")
+                    .text(fine.pretty_money())
+                    .reset()
+                    .text(" for your carry-on");
+
+                if left > 0 {
+                    msg = msg.text(". ").text(kept_on_board(left));
+                }
+
+                Some((
+                    GameEvent::CustomsFined {
+                        location,
+                        cargo: logged,
+                        fine: fine.clone(),
+                    },
+                    Some(msg),
+                ))
+            }
+            CustomsOutcome::Bribe(bribe) => {
+                let msg = msg
+                    .color(Color::Red)
+                    .text("customs is holding your carry-on. ")
+                    .reset()
+                    .text("`bribe` today to pay ")
+                    .color(Color::Green)
+                    .text(bribe.pretty_money())
+                    .reset()
+                    .text(" and get it back");
+
+                self.customs_hold = Some(CustomsHold {
+                    location: self.location.clone(),
+                    cargo,
+                    bribe: bribe.clone(),
+                });
+
+                Some((
+                    GameEvent::CustomsHeld {
+                        location,
+                        cargo: logged,
+                        bribe,
+                    },
+                    Some(msg),
+                ))
+            }
+        }
+    }
+
+    pub fn bribe(&mut self) -> Result<Vec<String>> {
+        let Some(hold) = self.customs_hold.take() else {
+            return Err(Error::NoCustomsHold);
+        };
+
+        if self.money < hold.bribe {
+            self.customs_hold = Some(hold);
+            return Err(Error::NotEnoughMoney);
+        }
+
+        // holds keep the dealer where the cargo is
+        self.money -= &hold.bribe;
+        let left = self.stash(hold.cargo);
+
+        let mut lines = vec![Msg::new()
+            .text("you paid ")
+            .color(Color::Green)
+            .text(hold.bribe.pretty_money())
+            .reset()
+            .text(" and customs released your carry-on in ")
+            .color(Color::Purple)
+            .text(&hold.location.name)
+            .reset()
+            .to_string()];
+
+        if left > 0 {
+            lines.push(kept_on_board(left));
+        }
+
+        Ok(lines)
+    }
+}
+
+fn kept_on_board(left: usize) -> String {
+    format!(
+        "there's no room for {} units here, they stay in your carry-on",
+        left.pretty_amount()
+    )
+}
+
+/// Adds `other` to `owned`, averaging what was paid for each element.
+pub fn merge_owned<E: Element>(
+    owned: &mut HashMap<Arc<E>, OwnedElement>,
+    other: HashMap<Arc<E>, OwnedElement>,
+) {
+    for (elem, other) in other {
+        if other.amount == 0 {
+            continue;
+        }
+
+        let owned = owned.entry(elem).or_insert(OwnedElement {
+            amount: 0,
+            bought_at: Money::default(),
+        });
+
+        owned.bought_at = (owned.bought_at.clone() * owned.amount + other.bought_at * other.amount)
+            / (owned.amount + other.amount);
+        owned.amount += other.amount;
+    }
+}
+
+fn take_owned<E: Element>(
+    owned: &mut HashMap<Arc<E>, OwnedElement>,
+    elem: &Arc<E>,
+    amount: usize,
+) -> Result<OwnedElement> {
+    let Some(owned_elem) = owned.get_mut(elem).filter(|owned| owned.amount >= amount) else {
+        return Err(Error::NotEnoughElement(elem.name().to_owned()));
+    };
+
+    owned_elem.amount -= amount;
+    let bought_at = owned_elem.bought_at.clone();
+
+    if owned_elem.amount == 0 {
+        owned.remove(elem);
+    }
+
+    Ok(OwnedElement { amount, bought_at })
 }
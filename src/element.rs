@@ -1,46 +1,150 @@
-use std::{hash::Hash, sync::Arc};
+use std::{hash::Hash, sync::Arc, time::SystemTime};
 
-use num_bigint::BigInt;
+use crate::{
+    money::Money,
+    resources::{Drug, Item, Location, TravelMode},
+};
 
-use crate::resources::{Drug, Item, Location};
+/// Relative price move caused by trading the whole supply (or demand) at once.
+const PRICE_IMPACT: f64 = 0.5;
+/// Dumping can't push a price below this fraction of the day's price.
+const MIN_PRICE_MULTIPLIER: f64 = 0.1;
 
-pub trait Element: Eq + Hash + 'static {
+pub trait Element: Eq + Hash + Send + Sync + 'static {
     fn name(&self) -> &str;
+
+    fn aliases(&self) -> &[String] {
+        &[]
+    }
 }
 
 impl Element for Drug {
     fn name(&self) -> &str {
         &self.name
     }
+
+    fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
 }
 impl Element for Item {
     fn name(&self) -> &str {
         &self.name
     }
+
+    fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
 }
 impl Element for Location {
     fn name(&self) -> &str {
         &self.name
     }
+
+    fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+}
+impl Element for TravelMode {
+    fn name(&self) -> &str {
+        &self.name
+    }
 }
 
 pub trait ArcElement: Eq + Hash + 'static {
     fn name(&self) -> &str;
+    fn aliases(&self) -> &[String];
 }
 
 impl<E: Element> ArcElement for Arc<E> {
     fn name(&self) -> &str {
         self.as_ref().name()
     }
+
+    fn aliases(&self) -> &[String] {
+        self.as_ref().aliases()
+    }
 }
 
 pub struct OwnedElement {
     pub amount: usize,
-    pub bought_at: BigInt,
+    pub bought_at: Money,
+}
+
+pub struct Quote {
+    pub amount: usize,
+    pub unit_price: Money,
+    pub total: Money,
+    pub impact: f64,
 }
 
 pub struct MarketElement {
     pub supply: usize,
     pub demand: usize,
-    pub price: BigInt,
+    pub price: Money,
+    pub impact: f64,
+    pub last_trade: SystemTime,
+}
+
+impl MarketElement {
+    pub fn new(supply: usize, demand: usize, price: Money) -> Self {
+        Self {
+            supply,
+            demand,
+            price,
+            impact: 0.,
+            last_trade: SystemTime::now(),
+        }
+    }
+
+    /// What's left of the trade impact, it fades out linearly over a day.
+    pub fn current_impact(&self, day_duration: u32) -> f64 {
+        let elapsed = self.last_trade.elapsed().unwrap_or_default().as_secs_f64();
+
+        let recovered = (elapsed / day_duration.max(1) as f64).min(1.);
+        self.impact * (1. - recovered)
+    }
+
+    pub fn current_price(&self, day_duration: u32) -> Money {
+        self.price.scale(1. + self.current_impact(day_duration))
+    }
+
+    pub fn buy_quote(&self, amount: usize, day_duration: u32) -> Quote {
+        let start = self.current_impact(day_duration);
+        let end = start + PRICE_IMPACT * amount as f64 / self.supply.max(1) as f64;
+
+        self.quote(amount, start, end)
+    }
+
+    pub fn sell_quote(&self, amount: usize, day_duration: u32) -> Quote {
+        let start = self.current_impact(day_duration);
+        let end = (start - PRICE_IMPACT * amount as f64 / self.demand.max(1) as f64)
+            .max(MIN_PRICE_MULTIPLIER - 1.);
+
+        self.quote(amount, start, end)
+    }
+
+    pub fn apply_buy(&mut self, quote: &Quote) {
+        self.supply -= quote.amount;
+        self.impact = quote.impact;
+        self.last_trade = SystemTime::now();
+    }
+
+    pub fn apply_sell(&mut self, quote: &Quote) {
+        self.demand -= quote.amount;
+        self.impact = quote.impact;
+        self.last_trade = SystemTime::now();
+    }
+
+    fn quote(&self, amount: usize, start: f64, end: f64) -> Quote {
+        // every unit moves the price a bit, so the fill is the average of both ends
+        let unit_price = self.price.scale(1. + (start + end) / 2.);
+
+        Quote {
+            amount,
+            total: unit_price.clone() * amount,
+            unit_price,
+            impact: end,
+        }
+    }
 }
@@ -8,6 +8,8 @@ pub type Error = DrugWarsError;
 pub enum DrugWarsError {
     #[error("Io error")]
     Io(#[from] std::io::Error),
+    #[error("Yaml error")]
+    Yaml(#[from] serde_yaml::Error),
     #[error("Parse int error")]
     ParseIntError(#[from] std::num::ParseIntError),
     #[error("Parse float error")]
@@ -20,12 +22,64 @@ pub enum DrugWarsError {
     DealerNotAvailable(String, String),
     #[error("couldn't find {0}")]
     ElementNotFound(String),
-    #[error("{0} is too ambiguous. try to be more precise")]
-    ElementAmbiguous(String),
+    #[error("{0} is too ambiguous, it could be {}", .1.join(" / "))]
+    ElementAmbiguous(String, Vec<String>),
     #[error("you don't have enough money you broke ass punk")]
     NotEnoughMoney,
     #[error("Invalid element {0}")]
     InvalidElement(String),
+    #[error("wrong arguments. try h for the command list")]
+    InvalidArguments,
+    #[error("there's not enough {0} on the market")]
+    NotEnoughSupply(String),
+    #[error("nobody here wants that much {0}")]
+    NotEnoughDemand(String),
+    #[error("you don't have enough {0}")]
+    NotEnoughElement(String),
+    #[error("you don't have enough room, you can only carry {0} more")]
+    NotEnoughCapacity(usize),
+    #[error("average price would be {0}, that's past your limit")]
+    PriceLimitReached(String),
+    #[error("there's no {1} going to {0}")]
+    TravelModeUnavailable(String, String),
+    #[error("you're not on a layover")]
+    NotOnLayover,
+    #[error("customs isn't holding anything of yours")]
+    NoCustomsHold,
+    #[error("customs is still holding your carry-on. `bribe` or wait for tomorrow")]
+    CustomsHoldOpen,
+    #[error("\"{0}\" isn't an amount. try 10, 1.5k, 50%, all, max or $1,000")]
+    InvalidAmount(String),
+    #[error("\"{0}\" isn't a sum of money. try 1000, $1,000 or 1.5k")]
+    InvalidMoney(String),
+    #[error("\"{0}\" isn't a number. try 10")]
+    InvalidNumber(String),
+    #[error("that doesn't even add up to one")]
+    AmountTooSmall,
+    #[error("line {0} of the event log is unreadable: {1}")]
+    InvalidLogEntry(usize, String),
+    #[error("event {0} doesn't replay: {1}")]
+    Replay(usize, String),
+    #[error("{0}")]
+    Diverged(String),
+    #[error("\"{0}\" isn't a date. try 1993-04-20")]
+    InvalidDate(String),
+    #[error("there's no snapshot of {0}")]
+    NoSnapshot(String),
+    #[error("you can only roll back to a day that already started")]
+    RollbackInFuture,
+    #[error("that's for admins only")]
+    NotAdmin,
+    #[error("\"{0}\" isn't somewhere to send output. try channel, notice or pm")]
+    InvalidRoute(String),
+    #[error("there's no {0} setting. try h for the command list")]
+    UnknownPreference(String),
+    #[error("\"{0}\" isn't a width. pick one between {1} and {2}")]
+    InvalidWidth(String, usize, usize),
+    #[error("\"{0}\" isn't on or off")]
+    InvalidSwitch(String),
+    #[error("markets can't be sorted by \"{0}\". try name, price, supply or demand")]
+    InvalidSort(String),
 }
 
 impl IntoResponse for DrugWarsError {
@@ -0,0 +1,404 @@
+use std::{
+    any::TypeId,
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    customs::Cargo,
+    element::Element,
+    error::{Error, Result},
+    metrics::METRICS,
+    money::{decimal, Money},
+    resources::Drug,
+    utils::{PrettyAmount, PrettyMoney},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ElementKind {
+    Drug,
+    Item,
+}
+
+impl ElementKind {
+    pub fn of<E: Element>() -> Self {
+        match TypeId::of::<E>() == TypeId::of::<Drug>() {
+            true => ElementKind::Drug,
+            false => ElementKind::Item,
+        }
+    }
+}
+
+/// Drug names and amounts, as carried through customs.
+pub type LoggedCargo = BTreeMap<String, usize>;
+
+pub fn log_cargo(cargo: &Cargo) -> LoggedCargo {
+    cargo
+        .iter()
+        .map(|(drug, owned)| (drug.name.clone(), owned.amount))
+        .collect()
+}
+
+fn new_markets() -> bool {
+    true
+}
+
+/// Something that changed the game. Locations, drugs, items and travel
+/// modes are logged by name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GameEvent {
+    /// The bot started, on a new game or on the save that covered the first
+    /// `resumed_from` events.
+    GameStarted {
+        seed: u64,
+        resumed_from: Option<usize>,
+        /// The markets were drawn anew, as they always were before saves
+        /// kept them.
+        #[serde(default = "new_markets")]
+        new_markets: bool,
+    },
+    Registered {
+        location: String,
+        #[serde(with = "decimal")]
+        money: Money,
+    },
+    Bought {
+        kind: ElementKind,
+        element: String,
+        amount: usize,
+        #[serde(with = "decimal")]
+        total: Money,
+        location: String,
+    },
+    Sold {
+        kind: ElementKind,
+        element: String,
+        amount: usize,
+        #[serde(with = "decimal")]
+        total: Money,
+        location: String,
+    },
+    Departed {
+        from: String,
+        destination: String,
+        mode: String,
+        #[serde(with = "decimal")]
+        price: Money,
+        arrival: NaiveDate,
+        via: Vec<String>,
+    },
+    Layover {
+        location: String,
+    },
+    /// Back on board after a layover.
+    LeftLayover {
+        location: String,
+    },
+    Stayed {
+        location: String,
+    },
+    Landed {
+        location: String,
+    },
+    Robbed {
+        mode: String,
+        #[serde(with = "decimal")]
+        lost: Money,
+    },
+    Packed {
+        drug: String,
+        amount: usize,
+    },
+    Unpacked {
+        drug: String,
+        amount: usize,
+    },
+    CustomsCleared {
+        location: String,
+        cargo: LoggedCargo,
+    },
+    CustomsSeized {
+        location: String,
+        cargo: LoggedCargo,
+    },
+    /// Seized for a fine the dealer couldn't pay.
+    FineUnpaid {
+        location: String,
+        cargo: LoggedCargo,
+        #[serde(with = "decimal")]
+        fine: Money,
+    },
+    CustomsFined {
+        location: String,
+        cargo: LoggedCargo,
+        #[serde(with = "decimal")]
+        fine: Money,
+    },
+    CustomsHeld {
+        location: String,
+        cargo: LoggedCargo,
+        #[serde(with = "decimal")]
+        bribe: Money,
+    },
+    Bribed {
+        location: String,
+        #[serde(with = "decimal")]
+        bribe: Money,
+    },
+    /// The bribe wasn't paid in time.
+    HoldKept {
+        location: String,
+    },
+    DayStarted,
+    DaysSkipped {
+        days: u32,
+    },
+    GameSaved,
+    /// An admin put the game back as it was at the start of the day, on the
+    /// snapshot that covered the first `resumed_from` events.
+    RolledBack {
+        resumed_from: Option<usize>,
+    },
+    /// A dealer changed one of their preferences.
+    PreferenceSet {
+        name: String,
+        value: String,
+    },
+}
+
+impl GameEvent {
+    pub fn describe(&self) -> String {
+        let cargo = |cargo: &LoggedCargo| {
+            cargo
+                .iter()
+                .map(|(drug, amount)| format!("{} {}", amount.pretty_amount(), drug))
+                .join(", ")
+        };
+
+        match self {
+            GameEvent::GameStarted { resumed_from, .. } => match resumed_from {
+                Some(position) => format!("game resumed after event {}", position),
+                None => "game started".to_owned(),
+            },
+            GameEvent::Registered { location, money } => {
+                format!("registered in {} with {}", location, money.pretty_money())
+            }
+            GameEvent::Bought {
+                element,
+                amount,
+                total,
+                location,
+                ..
+            } => format!(
+                "bought {} {} for {} in {}",
+                amount.pretty_amount(),
+                element,
+                total.pretty_money(),
+                location
+            ),
+            GameEvent::Sold {
+                element,
+                amount,
+                total,
+                location,
+                ..
+            } => format!(
+                "sold {} {} for {} in {}",
+                amount.pretty_amount(),
+                element,
+                total.pretty_money(),
+                location
+            ),
+            GameEvent::Departed {
+                from,
+                destination,
+                mode,
+                price,
+                arrival,
+                via,
+            } => format!(
+                "took a {} from {} to {}{} for {}, arriving {}",
+                mode,
+                from,
+                destination,
+                match via.is_empty() {
+                    true => String::new(),
+                    false => format!(" via {}", via.join(", ")),
+                },
+                price.pretty_money(),
+                arrival.format("%Y-%m-%d")
+            ),
+            GameEvent::Layover { location } => format!("stopped over in {}", location),
+            GameEvent::LeftLayover { location } => format!("left {}", location),
+            GameEvent::Stayed { location } => format!("stayed in {}", location),
+            GameEvent::Landed { location } => format!("landed in {}", location),
+            GameEvent::Robbed { mode, lost } => {
+                format!("got robbed of {} on the {}", lost.pretty_money(), mode)
+            }
+            GameEvent::Packed { drug, amount } => {
+                format!("packed {} {}", amount.pretty_amount(), drug)
+            }
+            GameEvent::Unpacked { drug, amount } => {
+                format!("unpacked {} {}", amount.pretty_amount(), drug)
+            }
+            GameEvent::CustomsCleared {
+                location,
+                cargo: cleared,
+            } => format!("customs in {} let {} through", location, cargo(cleared)),
+            GameEvent::CustomsSeized {
+                location,
+                cargo: seized,
+            } => format!("customs in {} seized {}", location, cargo(seized)),
+            GameEvent::FineUnpaid {
+                location,
+                cargo: seized,
+                fine,
+            } => format!(
+                "customs in {} seized {} over an unpaid {} fine",
+                location,
+                cargo(seized),
+                fine.pretty_money()
+            ),
+            GameEvent::CustomsFined {
+                location,
+                cargo: fined,
+                fine,
+            } => format!(
+                "customs in {} fined {} for {}",
+                location,
+                fine.pretty_money(),
+                cargo(fined)
+            ),
+            GameEvent::CustomsHeld {
+                location,
+                cargo: held,
+                bribe,
+            } => format!(
+                "customs in {} held {} for a {} bribe",
+                location,
+                cargo(held),
+                bribe.pretty_money()
+            ),
+            GameEvent::Bribed { location, bribe } => {
+                format!(
+                    "bribed customs in {} with {}",
+                    location,
+                    bribe.pretty_money()
+                )
+            }
+            GameEvent::HoldKept { location } => {
+                format!("customs in {} kept the carry-on", location)
+            }
+            GameEvent::DayStarted => "new day".to_owned(),
+            GameEvent::DaysSkipped { days } => format!("skipped {} days of downtime", days),
+            GameEvent::GameSaved => "saved the game".to_owned(),
+            GameEvent::RolledBack { .. } => "rolled the game back to this day".to_owned(),
+            GameEvent::PreferenceSet { name, value } => format!("set {} to {}", name, value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// In-game date.
+    pub day: NaiveDate,
+    pub timestamp: DateTime<Utc>,
+    /// Who did it, unset for things happening to the whole game.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nick: Option<String>,
+    #[serde(flatten)]
+    pub event: GameEvent,
+}
+
+/// Append-only record of everything that changed the game, one JSON object
+/// per line.
+pub struct EventLog {
+    path: PathBuf,
+    file: File,
+    /// How many events the log holds.
+    position: usize,
+}
+
+impl EventLog {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path.as_ref())?;
+        let position = BufReader::new(&file).lines().count();
+
+        Ok(Self {
+            path: path.as_ref().to_owned(),
+            file,
+            position,
+        })
+    }
+
+    /// Every event in the log, oldest first.
+    pub fn read_all(path: impl AsRef<Path>) -> Result<Vec<LogEntry>> {
+        let mut entries = vec![];
+
+        for (idx, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let entry = serde_json::from_str(&line?)
+                .map_err(|err| Error::InvalidLogEntry(idx + 1, err.to_string()))?;
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Appends an event. The game goes on if the log can't be written, it
+    /// only gets reported.
+    pub fn record(&mut self, day: NaiveDate, nick: Option<&str>, event: GameEvent) {
+        let entry = LogEntry {
+            day,
+            timestamp: Utc::now(),
+            nick: nick.map(str::to_owned),
+            event,
+        };
+
+        let written = serde_json::to_string(&entry)
+            .map_err(std::io::Error::from)
+            .and_then(|line| self.file.write_all(format!("{}\n", line).as_bytes()));
+
+        if let Some(nick) = nick {
+            METRICS.saw_dealer(nick);
+        }
+
+        match written {
+            Ok(()) => self.position += 1,
+            Err(err) => log::error!("couldn't write to the event log: {}", err),
+        }
+    }
+
+    /// The last `count` events of a dealer, oldest first.
+    pub fn recent(&self, nick: &str, count: usize) -> Result<Vec<LogEntry>> {
+        let mut entries = vec![];
+
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            let Ok(entry) = serde_json::from_str::<LogEntry>(&line?) else {
+                continue;
+            };
+
+            if entry.nick.as_deref() == Some(nick) {
+                entries.push(entry);
+            }
+        }
+
+        let skip = entries.len().saturating_sub(count);
+        Ok(entries.into_iter().skip(skip).collect())
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock},
+    time::SystemTime,
+};
+
+use ircie::{
+    system::IntoResponse,
+    system_params::{AnyArguments, Context, Res},
+    Irc, IrcPrefix,
+};
+
+use crate::{
+    error::Result,
+    frontend::{Command, Frontend, Outgoing, Route, Target, User},
+    game::Game,
+};
+
+/// The game, shared by the command systems and the rollover task.
+type SharedGame = Arc<Mutex<Game>>;
+
+/// A command that panicked doesn't stop the game for everyone else.
+fn lock(game: &SharedGame) -> MutexGuard<'_, Game> {
+    game.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Plays the game in the channels of an ircie bot.
+pub struct IrcFrontend {
+    pub config_path: String,
+}
+
+fn command(prefix: IrcPrefix, name: &str, arguments: &[&str]) -> Command {
+    Command {
+        user: User {
+            nick: prefix.nick.to_owned(),
+        },
+        name: name.to_owned(),
+        arguments: arguments
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect(),
+    }
+}
+
+/// ircie addresses plain responses to the sender, the flag turns that off.
+fn respond(outgoing: Outgoing) -> (bool, Vec<String>) {
+    (outgoing.target == Target::Sender, outgoing.lines)
+}
+
+/// Answers `nick` where their output should go. Announcements, notices and
+/// private messages are sent right away, leaving nothing to say back.
+fn reply(outgoing: Outgoing, nick: &str, context: &mut Context) -> (bool, Vec<String>) {
+    // for every channel, even when the command came in private
+    if outgoing.target == Target::Channel {
+        for line in &outgoing.lines {
+            context.privmsg_all(line);
+        }
+        return (false, vec![]);
+    }
+
+    match outgoing.route {
+        Route::Channel => respond(outgoing),
+        Route::Notice => {
+            for line in &outgoing.lines {
+                context.notice(nick, line);
+            }
+            (false, vec![])
+        }
+        Route::Private => {
+            for line in &outgoing.lines {
+                context.privmsg(nick, line);
+            }
+            (false, vec![])
+        }
+    }
+}
+
+/// One system per command: ircie tells systems apart by their function,
+/// not by the name they were registered with.
+macro_rules! command_systems {
+    ($irc:expr, $($name:literal => $system:ident),* $(,)?) => {
+        $(
+            fn $system(
+                prefix: IrcPrefix,
+                arguments: AnyArguments<'_>,
+                game: Res<SharedGame>,
+                mut context: Context,
+            ) -> impl IntoResponse {
+                let outgoing = lock(&game).handle(&command(prefix, $name, &arguments));
+                reply(outgoing, prefix.nick, &mut context)
+            }
+
+            $irc.add_system($name, $system).await;
+        )*
+    };
+}
+
+fn default_sys() -> impl IntoResponse {
+    "melp?"
+}
+
+fn show_help(prefix: IrcPrefix, game: Res<SharedGame>, mut context: Context) -> impl IntoResponse {
+    let outgoing = lock(&game).handle(&command(prefix, "h", &[]));
+    reply(outgoing, prefix.nick, &mut context)
+}
+
+fn publish(game: Res<SharedGame>) {
+    lock(&game).publish();
+}
+
+/// Sleeps until the next day is due and rolls it over, announcing it in
+/// the channels. Time spent down is caught up on the first wake.
+async fn roll_days(game: SharedGame, context: Arc<RwLock<Context>>) {
+    loop {
+        let due = lock(&game).next_rollover();
+        let wait = due.duration_since(SystemTime::now()).unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        let now = SystemTime::now().max(due);
+        let Some(outgoing) = lock(&game).tick(now) else {
+            continue;
+        };
+
+        let mut context = context.write().unwrap_or_else(PoisonError::into_inner);
+        for line in &outgoing.lines {
+            context.privmsg_all(line);
+        }
+    }
+}
+
+impl Frontend for IrcFrontend {
+    async fn run(&mut self, game: Game) -> Result<()> {
+        let mut irc = Irc::from_config(&self.config_path).await?;
+
+        // -- defaults
+        irc.add_default_system(default_sys)
+            .await
+            .add_invalid_system(show_help)
+            .await;
+
+        // -- resources
+        let game = Arc::new(Mutex::new(game));
+        irc.add_resource(game.clone()).await;
+
+        // -- intervals
+        irc.add_interval_task(std::time::Duration::from_secs(1), publish)
+            .await;
+
+        // -- rollovers
+        tokio::spawn(roll_days(game, irc.context()));
+
+        // -- systems
+        command_systems!(
+            irc,
+            "melp?" => melp,
+            "register" => register,
+            "i" => dealer_info,
+            "h" => help,
+            "m" => show_market,
+            "p" => show_people,
+            "cf" => check_flight_prices,
+            "f" => fly_to,
+            "stay" => stay,
+            "pack" => pack,
+            "unpack" => unpack,
+            "bribe" => bribe,
+            "t" => show_time,
+            "ph" => show_price_history,
+            "news" => show_news,
+            "leaderboard" => show_leaderboard,
+            "ha" => show_admin_help,
+            "save" => save,
+            "events" => show_events,
+            "rollback" => rollback,
+            "set" => set_preference,
+            "bd" => buy_drug,
+            "sd" => sell_drug,
+            "bi" => buy_item,
+            "si" => sell_item,
+            "cbd" => check_buy_drug,
+            "csd" => check_sell_drug,
+            "cbi" => check_buy_item,
+            "csi" => check_sell_item,
+        );
+
+        irc.run().await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+pub mod irc;
+pub mod scripted;
+
+use std::{fmt::Display, future::Future, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    game::Game,
+};
+
+/// Who sent a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User {
+    pub nick: String,
+}
+
+/// A command as it came in, without the transport's prefix.
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub user: User,
+    pub name: String,
+    pub arguments: Vec<String>,
+}
+
+impl Command {
+    /// Splits a line like `bd coke 10` into a command.
+    pub fn parse(nick: &str, line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+
+        Some(Self {
+            user: User {
+                nick: nick.to_owned(),
+            },
+            name: words.next()?.to_owned(),
+            arguments: words.map(str::to_owned).collect(),
+        })
+    }
+}
+
+/// Where outgoing lines go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// Back where the command came from, addressed to whoever sent it.
+    Sender,
+    /// Back where the command came from, as is. Boxes and tables go there.
+    Source,
+    /// Everyone playing, for what happens to the whole game.
+    Channel,
+}
+
+/// How replies get to whoever asked, when the transport has a choice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Route {
+    /// Said where the command came from, for everyone to see.
+    #[default]
+    Channel,
+    /// A notice to the sender, shown in the same window by most clients.
+    Notice,
+    /// A private message to the sender.
+    Private,
+}
+
+impl FromStr for Route {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "channel" => Ok(Route::Channel),
+            "notice" => Ok(Route::Notice),
+            "pm" | "private" | "query" => Ok(Route::Private),
+            _ => Err(Error::InvalidRoute(s.to_owned())),
+        }
+    }
+}
+
+impl Display for Route {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Route::Channel => write!(f, "channel"),
+            Route::Notice => write!(f, "notice"),
+            Route::Private => write!(f, "pm"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Outgoing {
+    pub target: Target,
+    /// Only a hint: broadcasts always go to the channel, and transports
+    /// without notices or private messages say everything in place.
+    pub route: Route,
+    pub lines: Vec<String>,
+}
+
+impl Outgoing {
+    pub fn new(target: Target, lines: impl IntoIterator<Item = impl ToString>) -> Self {
+        Self {
+            target,
+            route: Route::default(),
+            lines: lines.into_iter().map(|line| line.to_string()).collect(),
+        }
+    }
+
+    pub fn routed(mut self, route: Route) -> Self {
+        self.route = route;
+        self
+    }
+
+    pub fn to_sender(lines: impl IntoIterator<Item = impl ToString>) -> Self {
+        Self::new(Target::Sender, lines)
+    }
+
+    pub fn to_source(lines: impl IntoIterator<Item = impl ToString>) -> Self {
+        Self::new(Target::Source, lines)
+    }
+
+    pub fn to_channel(lines: impl IntoIterator<Item = impl ToString>) -> Self {
+        Self::new(Target::Channel, lines)
+    }
+}
+
+/// A chat backend the game runs on. It hands commands to `Game::handle`,
+/// sends back what comes out, and calls `Game::tick` once
+/// `Game::next_rollover` is due.
+pub trait Frontend {
+    /// Serves the game until the transport closes.
+    fn run(&mut self, game: Game) -> impl Future<Output = Result<()>>;
+}
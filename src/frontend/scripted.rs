@@ -0,0 +1,559 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    error::Result,
+    frontend::{Command, Frontend, Outgoing},
+    game::Game,
+};
+
+enum Step {
+    Say { nick: String, line: String },
+    Wait(Duration),
+}
+
+/// What was said during a scripted run, in order.
+#[derive(Debug, Clone)]
+pub enum Said {
+    Command(Command),
+    Outgoing(Outgoing),
+}
+
+/// Plays a fixed conversation in memory, on a clock that only moves when
+/// the script waits. The game and everything said are kept for checking
+/// afterwards.
+pub struct ScriptedFrontend {
+    steps: VecDeque<Step>,
+    now: SystemTime,
+    pub transcript: Vec<Said>,
+    pub game: Option<Game>,
+}
+
+impl ScriptedFrontend {
+    pub fn new() -> Self {
+        Self {
+            steps: VecDeque::default(),
+            now: SystemTime::now(),
+            transcript: vec![],
+            game: None,
+        }
+    }
+
+    /// `nick` types `line`, like `bd coke 10`.
+    pub fn say(mut self, nick: &str, line: &str) -> Self {
+        self.steps.push_back(Step::Say {
+            nick: nick.to_owned(),
+            line: line.to_owned(),
+        });
+        self
+    }
+
+    /// Lets time pass, rolling over whatever days are due.
+    pub fn wait(mut self, duration: Duration) -> Self {
+        self.steps.push_back(Step::Wait(duration));
+        self
+    }
+
+    /// Every line sent back, whatever the target.
+    pub fn lines(&self) -> Vec<&str> {
+        self.transcript
+            .iter()
+            .filter_map(|said| match said {
+                Said::Outgoing(outgoing) => Some(outgoing),
+                Said::Command(_) => None,
+            })
+            .flat_map(|outgoing| outgoing.lines.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// The lines the last command got back.
+    pub fn last_reply(&self) -> Option<&Outgoing> {
+        self.transcript.iter().rev().find_map(|said| match said {
+            Said::Outgoing(outgoing) => Some(outgoing),
+            Said::Command(_) => None,
+        })
+    }
+}
+
+impl Default for ScriptedFrontend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Frontend for ScriptedFrontend {
+    async fn run(&mut self, mut game: Game) -> Result<()> {
+        // the script's clock starts with the game's day
+        self.now = game.settings.timer;
+
+        while let Some(step) = self.steps.pop_front() {
+            match step {
+                Step::Say { nick, line } => {
+                    let Some(command) = Command::parse(&nick, &line) else {
+                        continue;
+                    };
+
+                    let outgoing = game.handle(&command);
+                    self.transcript.push(Said::Command(command));
+                    self.transcript.push(Said::Outgoing(outgoing));
+                }
+                Step::Wait(duration) => {
+                    self.now += duration;
+
+                    if let Some(outgoing) = game.tick(self.now) {
+                        self.transcript.push(Said::Outgoing(outgoing));
+                    }
+                }
+            }
+        }
+
+        game.publish();
+        self.game = Some(game);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::{
+        config::{get_game_data_from_config, get_settings_from_config},
+        dealer::DealerStatus,
+        event_log::{EventLog, GameEvent},
+        frontend::{Route, Target},
+        replay::verify_save,
+        utils::load_config,
+    };
+
+    /// A new game in its own directory, with the repo's config.
+    async fn new_game(name: &str) -> (Game, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("drugwars-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let config = load_config("drugwars_config.yaml").await.unwrap();
+        let game_data = get_game_data_from_config(&config);
+        let mut settings = get_settings_from_config(&config, "drugwars_config.yaml");
+
+        settings.seed = 1993;
+        settings.admins = vec!["admin".to_owned()];
+        settings.api_address = None;
+        settings.save_path = dir.join("save.yaml").to_str().unwrap().to_owned();
+        settings.event_log_path = dir.join("events.jsonl").to_str().unwrap().to_owned();
+        settings.snapshot_dir = dir.join("snapshots").to_str().unwrap().to_owned();
+
+        (Game::start(game_data, settings).await.unwrap(), dir)
+    }
+
+    async fn load_game_data() -> crate::resources::GameData {
+        let config = load_config("drugwars_config.yaml").await.unwrap();
+        get_game_data_from_config(&config)
+    }
+
+    fn day(game: &Game) -> Duration {
+        Duration::from_secs(game.settings.day_duration as u64)
+    }
+
+    #[tokio::test]
+    async fn registers_and_trades() {
+        let (game, dir) = new_game("trades").await;
+
+        let mut frontend = ScriptedFrontend::new()
+            .say("bob", "register")
+            .say("bob", "register")
+            .say("bob", "m");
+        frontend.run(game).await.unwrap();
+
+        let lines = frontend.lines();
+        assert!(lines[0].contains("Get Rich or Die Tryin"));
+        assert_eq!(lines[1], "You are already playing you donut");
+        assert_eq!(frontend.last_reply().unwrap().target, Target::Source);
+
+        // buy the first drug on sale wherever bob landed
+        let game = frontend.game.take().unwrap();
+        let dealer = game.state.dealers.get_dealer("bob").unwrap();
+        let data = game.state.locations.get(&dealer.location).unwrap();
+        let drug = data.drug_market.keys().next().unwrap().name.clone();
+
+        let mut frontend = ScriptedFrontend::new()
+            .say("bob", &format!("bd {} 10", drug))
+            .say("bob", &format!("sd {} 4", drug));
+        frontend.run(game).await.unwrap();
+
+        let game = frontend.game.as_ref().unwrap();
+        let dealer = game.state.dealers.get_dealer("bob").unwrap();
+        assert_eq!(dealer.get_total_owned_local::<crate::resources::Drug>(), 6);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn unknown_commands_get_the_help() {
+        let (game, dir) = new_game("help").await;
+
+        let mut frontend = ScriptedFrontend::new().say("bob", "dance");
+        frontend.run(game).await.unwrap();
+
+        let reply = frontend.last_reply().unwrap();
+        assert_eq!(reply.target, Target::Source);
+        assert!(reply.lines.iter().any(|line| line.contains("Command list")));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn admin_commands_need_an_admin() {
+        let (game, dir) = new_game("admin").await;
+
+        let mut frontend = ScriptedFrontend::new()
+            .say("bob", "register")
+            .say("bob", "save")
+            .say("bob", "events bob")
+            .say("bob", "rollback 1993-04-20 confirm")
+            .say("admin", "events bob");
+        frontend.run(game).await.unwrap();
+
+        let lines = frontend.lines();
+        assert_eq!(lines[1..4], ["that's for admins only"; 3]);
+        assert!(lines[4].contains("events of bob"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn restarts_keep_the_markets() {
+        let (game, dir) = new_game("restart").await;
+
+        let mut frontend = ScriptedFrontend::new()
+            .say("bob", "register")
+            .say("admin", "save");
+        frontend.run(game).await.unwrap();
+
+        let game = frontend.game.take().unwrap();
+        let markets = |game: &Game| {
+            game.game_data
+                .locations
+                .iter()
+                .map(|location| {
+                    let data = game.state.locations.get(location).unwrap();
+                    data.drug_market
+                        .iter()
+                        .map(|(drug, market)| {
+                            (
+                                drug.name.clone(),
+                                market.price.clone(),
+                                data.price_history
+                                    .get(drug)
+                                    .map_or(0, |history| history.len()),
+                            )
+                        })
+                        .sorted()
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let before = markets(&game);
+
+        let config = load_config("drugwars_config.yaml").await.unwrap();
+        let mut settings = get_settings_from_config(&config, "drugwars_config.yaml");
+        settings.save_path = game.settings.save_path.clone();
+        settings.event_log_path = game.settings.event_log_path.clone();
+        settings.snapshot_dir = game.settings.snapshot_dir.clone();
+        settings.api_address = None;
+        drop(game);
+
+        let game = Game::start(load_game_data().await, settings).await.unwrap();
+        assert_eq!(markets(&game), before);
+
+        let mut frontend = ScriptedFrontend::new().say("admin", "save");
+        frontend.run(game).await.unwrap();
+        let game = frontend.game.take().unwrap();
+
+        let mut settings = get_settings_from_config(&config, "drugwars_config.yaml");
+        settings.save_path = game.settings.save_path.clone();
+        settings.event_log_path = game.settings.event_log_path.clone();
+
+        let differences = verify_save(&game.game_data, settings, None).await.unwrap();
+        assert!(differences.is_empty(), "{:?}", differences);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn days_roll_over_on_the_clock() {
+        let (game, dir) = new_game("days").await;
+        let start = game.settings.current_day;
+        let day = day(&game);
+
+        let mut frontend = ScriptedFrontend::new()
+            .say("bob", "register")
+            .wait(day / 2)
+            .wait(day / 2)
+            .wait(day * 2);
+        frontend.run(game).await.unwrap();
+
+        let broadcasts = frontend
+            .transcript
+            .iter()
+            .filter(|said| matches!(said, Said::Outgoing(outgoing) if outgoing.target == Target::Channel))
+            .count();
+        assert_eq!(broadcasts, 2);
+
+        let game = frontend.game.as_ref().unwrap();
+        assert_eq!(game.settings.current_day, start + chrono::Duration::days(3));
+        assert_eq!(
+            game.state.dealers.get_dealer("bob").unwrap().status,
+            DealerStatus::Available
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn flights_land_and_replay_to_the_save() {
+        let (game, dir) = new_game("flights").await;
+        let day = day(&game);
+
+        let mut frontend = ScriptedFrontend::new().say("bob", "register");
+        frontend.run(game).await.unwrap();
+
+        let game = frontend.game.take().unwrap();
+        let dealer = game.state.dealers.get_dealer("bob").unwrap();
+        let destination = game
+            .game_data
+            .locations
+            .iter()
+            .find(|location| location.name != dealer.location.name)
+            .unwrap()
+            .name
+            .clone();
+        // typed like a player would, "f beijing, china plane"
+        let typed = format!("f {} plane", destination.to_lowercase());
+
+        let mut frontend = ScriptedFrontend::new()
+            .say("bob", &typed)
+            .wait(day * 10)
+            .say("admin", "save");
+        frontend.run(game).await.unwrap();
+
+        let game = frontend.game.take().unwrap();
+        let dealer = game.state.dealers.get_dealer("bob").unwrap();
+        assert_eq!(dealer.location.name, destination);
+        assert_eq!(dealer.status, DealerStatus::Available);
+
+        let entries = EventLog::read_all(&game.settings.event_log_path).unwrap();
+        assert!(entries
+            .iter()
+            .any(|entry| matches!(&entry.event, GameEvent::Landed { location } if *location == destination)));
+
+        let config = load_config("drugwars_config.yaml").await.unwrap();
+        let mut settings = get_settings_from_config(&config, "drugwars_config.yaml");
+        settings.save_path = game.settings.save_path.clone();
+        settings.event_log_path = game.settings.event_log_path.clone();
+
+        let differences = verify_save(&game.game_data, settings, None).await.unwrap();
+        assert!(differences.is_empty(), "{:?}", differences);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn cargo_packed_on_a_layover_goes_through_customs() {
+        let (game, dir) = new_game("layover").await;
+        let day = day(&game);
+
+        let mut frontend = ScriptedFrontend::new().say("bob", "register");
+        frontend.run(game).await.unwrap();
+
+        let mut game = frontend.game.take().unwrap();
+        let dealer = game.state.dealers.get_dealer("bob").unwrap();
+        let plane = &game.game_data.travel_modes[0];
+        let (destination, layover) = game
+            .game_data
+            .locations
+            .iter()
+            .find_map(|location| {
+                let route = game
+                    .game_data
+                    .routes
+                    .find(&dealer.location, location, plane)?;
+                (route.len() > 1).then(|| (location.name.clone(), route[0].name.clone()))
+            })
+            .unwrap();
+
+        let mut frontend =
+            ScriptedFrontend::new().say("bob", &format!("f {} plane", destination.to_lowercase()));
+        frontend.run(game).await.unwrap();
+        game = frontend.game.take().unwrap();
+
+        while game.state.flights["bob"].layover.is_none() {
+            let mut frontend = ScriptedFrontend::new().wait(day);
+            frontend.run(game).await.unwrap();
+            game = frontend.game.take().unwrap();
+        }
+
+        let dealer = game.state.dealers.get_dealer("bob").unwrap();
+        assert_eq!(dealer.location.name, layover);
+
+        // the cheapest drug on sale there, bought and packed during the stop
+        let drug = game.state.locations[&dealer.location]
+            .drug_market
+            .iter()
+            .filter(|(drug, market)| market.supply >= 5 && !drug.name.contains(' '))
+            .min_by_key(|(_, market)| market.price.clone())
+            .map(|(drug, _)| drug.name.clone())
+            .unwrap();
+
+        let mut frontend = ScriptedFrontend::new()
+            .say("bob", &format!("bd {} 5", drug))
+            .say("bob", &format!("pack {} all", drug))
+            .wait(day * 10)
+            .say("admin", "save");
+        frontend.run(game).await.unwrap();
+
+        let game = frontend.game.take().unwrap();
+        let dealer = game.state.dealers.get_dealer("bob").unwrap();
+        assert_eq!(dealer.location.name, destination);
+        assert!(dealer.carry_on.is_empty());
+
+        let entries = EventLog::read_all(&game.settings.event_log_path).unwrap();
+        assert!(entries
+            .iter()
+            .any(|entry| matches!(&entry.event, GameEvent::Packed { .. })));
+        assert!(entries.iter().any(|entry| matches!(
+            &entry.event,
+            GameEvent::CustomsCleared { location, .. }
+                | GameEvent::CustomsSeized { location, .. }
+                | GameEvent::CustomsFined { location, .. }
+                | GameEvent::FineUnpaid { location, .. }
+                | GameEvent::CustomsHeld { location, .. }
+                if *location == destination
+        )));
+
+        let config = load_config("drugwars_config.yaml").await.unwrap();
+        let mut settings = get_settings_from_config(&config, "drugwars_config.yaml");
+        settings.save_path = game.settings.save_path.clone();
+        settings.event_log_path = game.settings.event_log_path.clone();
+
+        let differences = verify_save(&game.game_data, settings, None).await.unwrap();
+        assert!(differences.is_empty(), "{:?}", differences);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tables_follow_the_output_setting() {
+        let (game, dir) = new_game("routes").await;
+        let policy = game.settings.bulky_output;
+
+        let mut frontend = ScriptedFrontend::new()
+            .say("bob", "register")
+            .say("bob", "m")
+            .say("bob", "set output pm")
+            .say("bob", "m")
+            .say("bob", "set output carrier-pigeon")
+            .say("admin", "save");
+        frontend.run(game).await.unwrap();
+
+        let replies = frontend
+            .transcript
+            .iter()
+            .filter_map(|said| match said {
+                Said::Outgoing(outgoing) => Some((outgoing.target, outgoing.route)),
+                Said::Command(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            replies,
+            [
+                (Target::Sender, Route::Channel),
+                (Target::Source, policy),
+                (Target::Sender, Route::Channel),
+                (Target::Source, Route::Private),
+                (Target::Sender, Route::Channel),
+                (Target::Sender, Route::Channel),
+            ]
+        );
+
+        let game = frontend.game.take().unwrap();
+        let dealer = game.state.dealers.get_dealer("bob").unwrap();
+        assert_eq!(dealer.preferences.output, Some(Route::Private));
+
+        let config = load_config("drugwars_config.yaml").await.unwrap();
+        let mut settings = get_settings_from_config(&config, "drugwars_config.yaml");
+        settings.save_path = game.settings.save_path.clone();
+        settings.event_log_path = game.settings.event_log_path.clone();
+
+        let differences = verify_save(&game.game_data, settings, None).await.unwrap();
+        assert!(differences.is_empty(), "{:?}", differences);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn settings_shape_the_market() {
+        let (game, dir) = new_game("settings").await;
+
+        let mut frontend = ScriptedFrontend::new()
+            .say("bob", "register")
+            .say("bob", "set compact on")
+            .say("bob", "set colour off")
+            .say("bob", "set sort price")
+            .say("bob", "set width 10")
+            .say("bob", "set volume 11")
+            .say("bob", "m");
+        frontend.run(game).await.unwrap();
+
+        let lines = frontend.lines();
+        assert!(lines[4].contains("isn't a width"));
+        assert!(lines[5].contains("there's no volume setting"));
+
+        let market = frontend.last_reply().unwrap().lines.clone();
+        assert!(market
+            .iter()
+            .all(|line| !line.contains('│') && !line.contains('\x03')));
+
+        // cheapest drugs first
+        let game = frontend.game.take().unwrap();
+        let dealer = game.state.dealers.get_dealer("bob").unwrap();
+        let data = game.state.locations.get(&dealer.location).unwrap();
+        let by_price = data
+            .drug_market
+            .iter()
+            .sorted_by_key(|(drug, market)| {
+                (
+                    market.current_price(game.settings.day_duration),
+                    drug.name.clone(),
+                )
+            })
+            .map(|(drug, _)| drug.name.clone())
+            .collect::<Vec<_>>();
+        let listed = market
+            .iter()
+            .filter_map(|line| by_price.iter().find(|name| line.starts_with(name.as_str())))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(listed, by_price);
+
+        let mut frontend = ScriptedFrontend::new().say("admin", "save");
+        frontend.run(game).await.unwrap();
+        let game = frontend.game.take().unwrap();
+
+        let config = load_config("drugwars_config.yaml").await.unwrap();
+        let mut settings = get_settings_from_config(&config, "drugwars_config.yaml");
+        settings.save_path = game.settings.save_path.clone();
+        settings.event_log_path = game.settings.event_log_path.clone();
+
+        let differences = verify_save(&game.game_data, settings, None).await.unwrap();
+        assert!(differences.is_empty(), "{:?}", differences);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
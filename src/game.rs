@@ -0,0 +1,438 @@
+use std::time::{Instant, SystemTime};
+
+use chrono::{Duration, NaiveDate};
+use ircie::format::{Color, Msg};
+use itertools::Itertools;
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    api::Api,
+    commands::{show_help, COMMANDS},
+    config::Settings,
+    dealer::{merge_owned, Dealer, DealerStatus},
+    error::Result,
+    event_log::{EventLog, GameEvent},
+    frontend::{Command, Outgoing, Target},
+    metrics::METRICS,
+    preferences::{Preferences, View},
+    render::render_daily_summary,
+    resources::{DrugWarsRng, GameData, TravelMode},
+    save::{load_save, SaveData},
+    scheduler::{next_rollover, plan_rollover, DayContext, DayHook},
+    snapshot::take_snapshot,
+    state::GameState,
+    utils::{PrettyMoney, StringManips},
+};
+
+/// The whole game, whatever it's played over.
+pub struct Game {
+    pub settings: Settings,
+    pub game_data: GameData,
+    pub state: GameState,
+    pub rng: DrugWarsRng,
+    pub log: EventLog,
+    pub api: Api,
+}
+
+impl Game {
+    /// Picks up the saved game, or starts a new one.
+    pub async fn start(game_data: GameData, mut settings: Settings) -> Result<Self> {
+        let mut state = GameState::new(&game_data.locations);
+        let mut resumed_from = None;
+        let save = load_save(&settings.save_path).await?;
+        // a saved game carries on with the markets of its day
+        let new_markets = save.as_ref().is_none_or(|save| save.markets.is_none());
+
+        if let Some(save) = save {
+            resumed_from = save.log_position;
+            save.restore(&game_data, &mut settings, &mut state)?;
+        }
+
+        let mut rng = DrugWarsRng::for_startup(settings.seed, settings.current_day);
+        if new_markets {
+            state.locations.update(&game_data, &mut rng.0);
+        }
+
+        let mut log = EventLog::open(&settings.event_log_path)?;
+        log.record(
+            settings.current_day,
+            None,
+            GameEvent::GameStarted {
+                seed: settings.seed,
+                resumed_from,
+                new_markets,
+            },
+        );
+
+        let api = Api::new(&game_data);
+        api.publish(&settings, &game_data, &state);
+
+        Ok(Self {
+            settings,
+            game_data,
+            state,
+            rng,
+            log,
+            api,
+        })
+    }
+
+    /// Runs a command. Unknown ones get the help.
+    pub fn handle(&mut self, command: &Command) -> Outgoing {
+        let (name, run) = COMMANDS
+            .iter()
+            .find(|(name, _)| *name == command.name)
+            .copied()
+            .unwrap_or(("h", show_help));
+
+        let _timer = METRICS.command(name);
+        let arguments = command
+            .arguments
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+
+        let mut outgoing = match run(self, &command.user.nick, &arguments) {
+            Ok(outgoing) => outgoing,
+            Err(err) => return Outgoing::to_sender([err.to_string()]),
+        };
+
+        let preferences = self.preferences(&command.user.nick);
+
+        if !preferences.colour {
+            outgoing.lines = outgoing
+                .lines
+                .iter()
+                .map(|line| line.strip_formatting())
+                .collect();
+        }
+
+        // tables and boxes fill the channel, short answers stay there
+        match outgoing.target {
+            Target::Source => {
+                let route = preferences.output.unwrap_or(self.settings.bulky_output);
+                outgoing.routed(route)
+            }
+            Target::Sender | Target::Channel => outgoing,
+        }
+    }
+
+    /// What the dealer picked with `set`, the defaults for anyone else.
+    pub fn preferences(&self, nick: &str) -> Preferences {
+        self.state
+            .dealers
+            .get(nick)
+            .map(|dealer| dealer.preferences.clone())
+            .unwrap_or_default()
+    }
+
+    /// How renders should come out for that dealer.
+    pub fn view(&self, nick: &str) -> View {
+        self.preferences(nick).view(&self.settings)
+    }
+
+    /// When the next day is due, for the frontend to wake up at.
+    pub fn next_rollover(&self) -> SystemTime {
+        next_rollover(&self.settings)
+    }
+
+    /// Rolls over the days due at `now`, if any.
+    pub fn tick(&mut self, now: SystemTime) -> Option<Outgoing> {
+        let Game {
+            settings,
+            game_data,
+            state,
+            rng,
+            log,
+            ..
+        } = self;
+
+        let rollover = plan_rollover(settings, now)?;
+
+        let started = Instant::now();
+        settings.timer = rollover.day_start;
+
+        let mut lines = vec![];
+
+        if rollover.skipped > 0 {
+            log.record(
+                settings.current_day,
+                None,
+                GameEvent::DaysSkipped {
+                    days: rollover.skipped,
+                },
+            );
+
+            lines.push(
+                Msg::new()
+                    .color(Color::Red)
+                    .text(format!("skipped {} days of downtime", rollover.skipped)),
+            );
+        }
+
+        for _ in 0..rollover.days {
+            settings.current_day += Duration::days(1);
+
+            lines.push(
+                Msg::new()
+                    .text("new day: ")
+                    .color(Color::Green)
+                    .text(settings.current_day.format("%Y-%m-%d").to_string()),
+            );
+
+            log.record(settings.current_day, None, GameEvent::DayStarted);
+            rng.0 = DrugWarsRng::for_day(settings.seed, settings.current_day).0;
+
+            let mut day = DayContext {
+                settings,
+                game_data,
+                state,
+                rng: &mut rng.0,
+                lines: &mut lines,
+                log,
+            };
+
+            for hook in DAY_HOOKS {
+                hook(&mut day);
+            }
+        }
+
+        let mut lines = lines
+            .into_iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+
+        if settings.daily_summary {
+            let length = settings.daily_summary_length;
+            let yesterday = settings.current_day - Duration::days(1);
+
+            lines.extend(render_daily_summary(
+                settings.width,
+                &yesterday.format("%Y-%m-%d").to_string(),
+                &state.locations.price_movers(length),
+                &state
+                    .locations
+                    .settled_rumors()
+                    .into_iter()
+                    .take(length)
+                    .collect::<Vec<_>>(),
+                &state.dealers.died_on(yesterday),
+                &state.dealers.leaderboard(length),
+            ));
+        }
+
+        let save = SaveData::new(settings, state, log.position());
+
+        if let Err(err) = save.write(&settings.save_path) {
+            lines.push(
+                Msg::new()
+                    .color(Color::Red)
+                    .text(format!("couldn't save the game: {}", err))
+                    .to_string(),
+            );
+        }
+
+        if let Err(err) = take_snapshot(settings, &save) {
+            lines.push(
+                Msg::new()
+                    .color(Color::Red)
+                    .text(format!("couldn't keep a snapshot of the day: {}", err))
+                    .to_string(),
+            );
+        }
+
+        METRICS.observe_rollover(started.elapsed());
+
+        Some(Outgoing::to_channel(lines))
+    }
+
+    /// Refreshes what the API and the metrics serve.
+    pub fn publish(&self) {
+        self.api
+            .publish(&self.settings, &self.game_data, &self.state);
+        METRICS.update_game(&self.state);
+    }
+}
+
+fn rob_on_the_way(
+    msg: Msg,
+    dealer: &mut Dealer,
+    mode: &TravelMode,
+    rng: &mut StdRng,
+    log: &mut EventLog,
+    day: NaiveDate,
+) -> Msg {
+    if !rng.gen_bool(mode.risk) {
+        return msg;
+    }
+
+    let robbed = dealer.money.percent(rng.gen_range(10. ..30.));
+    dealer.money -= &robbed;
+
+    log.record(
+        day,
+        Some(&dealer.nick),
+        GameEvent::Robbed {
+            mode: mode.name.clone(),
+            lost: robbed.clone(),
+        },
+    );
+
+    msg.color(Color::Red)
+        .text(format!(". you got robbed on the {} and lost ", mode.name))
+        .text(robbed.pretty_money())
+}
+
+/// Per-day tasks, run in order for every day that begins.
+const DAY_HOOKS: &[DayHook] = &[update_markets, release_customs_holds, move_travellers];
+
+fn update_markets(day: &mut DayContext) {
+    day.state.locations.update(day.game_data, day.rng);
+}
+
+fn release_customs_holds(day: &mut DayContext) {
+    for dealer in day.state.dealers.values_mut() {
+        if let Some(hold) = dealer.customs_hold.take() {
+            day.log.record(
+                day.settings.current_day,
+                Some(&dealer.nick),
+                GameEvent::HoldKept {
+                    location: hold.location.name.clone(),
+                },
+            );
+
+            day.lines.push(
+                Msg::new()
+                    .text(format!("{}: ", dealer.nick))
+                    .color(Color::Red)
+                    .text(format!(
+                        "customs in {} kept your carry-on",
+                        hold.location.name
+                    )),
+            );
+        }
+    }
+}
+
+fn move_travellers(day: &mut DayContext) {
+    let current_day = day.settings.current_day;
+
+    let state = &mut *day.state;
+    // by nick, not the map's order: robberies draw from the day's generator
+    let travellers = state.flights.keys().cloned().sorted().collect::<Vec<_>>();
+
+    for nick in travellers.iter() {
+        let flight = state.flights.get_mut(nick).unwrap();
+        let dealer = state.dealers.get_dealer_mut(nick).unwrap();
+
+        if let Some(layover) = flight.layover.take() {
+            dealer.status = DealerStatus::Flying;
+            // whatever was packed during the stop goes through customs too
+            merge_owned(&mut flight.cargo, std::mem::take(&mut dealer.carry_on));
+
+            day.log.record(
+                current_day,
+                Some(nick),
+                GameEvent::LeftLayover {
+                    location: layover.name.clone(),
+                },
+            );
+
+            let data = state.locations.get_mut(&layover).unwrap();
+            data.people.remove(&dealer.nick);
+        }
+
+        if flight
+            .layovers
+            .front()
+            .is_none_or(|(_, arrival)| *arrival > current_day)
+        {
+            continue;
+        }
+
+        let (layover, _) = flight.layovers.pop_front().unwrap();
+
+        let msg = Msg::new()
+            .text(format!("{}: ", dealer.nick))
+            .color(Color::Green)
+            .text("layover at ")
+            .color(Color::Purple)
+            .text(&layover.name)
+            .color(Color::Green)
+            .text(" on your way to ")
+            .color(Color::Purple)
+            .text(&flight.destination.name)
+            .reset()
+            .text(". you leave tomorrow, `stay` to stay here");
+
+        day.lines.push(rob_on_the_way(
+            msg,
+            dealer,
+            &flight.mode,
+            day.rng,
+            day.log,
+            current_day,
+        ));
+        dealer.location = layover.clone();
+        dealer.status = DealerStatus::Available;
+
+        day.log.record(
+            current_day,
+            Some(nick),
+            GameEvent::Layover {
+                location: layover.name.clone(),
+            },
+        );
+
+        let data = state.locations.get_mut(&layover).unwrap();
+        data.people.insert(dealer.nick.clone());
+        flight.layover = Some(layover);
+    }
+
+    let landed = state
+        .flights
+        .iter()
+        .filter(|(_, flight)| flight.arrival <= current_day)
+        .map(|(nick, _)| nick.clone())
+        .sorted()
+        .collect::<Vec<_>>();
+
+    for nick in landed.iter() {
+        let flight = state.flights.remove(nick).unwrap();
+        let dealer = state.dealers.get_dealer_mut(nick).unwrap();
+
+        let msg = Msg::new()
+            .text(format!("{}: ", dealer.nick))
+            .color(Color::Green)
+            .text("you arrived at ")
+            .color(Color::Purple)
+            .text(&flight.destination.name);
+
+        day.lines.push(rob_on_the_way(
+            msg,
+            dealer,
+            &flight.mode,
+            day.rng,
+            day.log,
+            current_day,
+        ));
+        dealer.location = flight.destination.clone();
+        dealer.status = DealerStatus::Available;
+
+        day.log.record(
+            current_day,
+            Some(nick),
+            GameEvent::Landed {
+                location: flight.destination.name.clone(),
+            },
+        );
+
+        if let Some((event, msg)) = dealer.clear_customs(flight.cargo, day.rng) {
+            day.log.record(current_day, Some(nick), event);
+            day.lines.extend(msg);
+        }
+
+        let data = state.locations.get_mut(&flight.destination).unwrap();
+        data.people.insert(dealer.nick.clone());
+    }
+}
@@ -1,30 +1,49 @@
 use std::{
-    any::TypeId,
-    borrow::Borrow,
-    collections::{HashMap, HashSet},
-    hash::Hash,
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet, VecDeque},
     ops::{Deref, DerefMut},
-    sync::{Arc, RwLock},
+    sync::Arc,
 };
 
-use rand::{seq::IteratorRandom, Rng, RngCore};
+use ircie::format::{Color, Msg};
+use rand::{
+    seq::{IteratorRandom, SliceRandom},
+    Rng, RngCore,
+};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    element::{ArcElement, Element, MarketElement},
+    element::{Element, MarketElement},
     error::{Error, Result},
-    resources::{Drug, Drugs, GameData, Item, Items, Location, Locations},
+    money::Money,
+    resources::{Drug, DrugProfile, Drugs, GameData, Item, Items, Location, Locations, Messages},
+    utils::get_distance,
 };
 
-#[derive(Debug, Clone)]
+/// How many days of drug prices each location remembers.
+pub const PRICE_HISTORY_DAYS: usize = 30;
+/// How many past days of news each location remembers.
+pub const NEWS_ARCHIVE_DAYS: usize = 7;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PriceTrend {
     Up,
     Down,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PriceModKind {
     Rumor,
     Spontaneous,
+    Event { name: String, origin: String },
+}
+
+/// How a price mod fades out over its duration.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Decay {
+    Constant,
+    Linear,
+    Exponential,
 }
 
 #[derive(Debug, Clone)]
@@ -32,6 +51,58 @@ pub struct PriceMod {
     pub drug: Arc<Drug>,
     pub trend: PriceTrend,
     pub kind: PriceModKind,
+    pub duration: u32,
+    pub age: u32,
+    pub decay: Decay,
+    /// Scales the whole mod, lower for locations an event spread to.
+    pub strength: f64,
+}
+
+impl PriceMod {
+    pub fn new(drug: Arc<Drug>, trend: PriceTrend, kind: PriceModKind) -> Self {
+        Self {
+            drug,
+            trend,
+            kind,
+            duration: 1,
+            age: 0,
+            decay: Decay::Constant,
+            strength: 1.,
+        }
+    }
+
+    pub fn current_strength(&self) -> f64 {
+        let decay = match self.decay {
+            Decay::Constant => 1.,
+            Decay::Linear => 1. - self.age as f64 / self.duration.max(1) as f64,
+            Decay::Exponential => 0.5f64.powi(self.age as i32),
+        };
+
+        self.strength * decay
+    }
+
+    pub fn multiplier(&self, profile: &DrugProfile) -> f64 {
+        let strength = self.current_strength();
+
+        match self.trend {
+            PriceTrend::Up => 1. + (profile.spike_up - 1.) * strength,
+            PriceTrend::Down => 1. / (1. + (profile.spike_down - 1.) * strength),
+        }
+    }
+}
+
+pub struct PriceMove {
+    pub location: Arc<Location>,
+    pub drug: Arc<Drug>,
+    pub from: Money,
+    pub to: Money,
+}
+
+impl PriceMove {
+    /// Change in percent.
+    pub fn change(&self) -> f64 {
+        (&self.to - &self.from).ratio(&self.from) * 100.
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -45,30 +116,68 @@ pub struct Rumor {
 pub struct SingleLocationData {
     pub drug_market: HashMap<Arc<Drug>, MarketElement>,
     pub item_market: HashMap<Arc<Item>, MarketElement>,
-    pub messages: Vec<String>,
+    /// Today's headlines, rolled once a day so every market view agrees.
+    pub news: Vec<String>,
+    /// Headlines of the previous days, most recent first.
+    pub news_archive: VecDeque<Vec<String>>,
     pub people: HashSet<String>,
     pub price_mods: Vec<PriceMod>,
     pub rumors: Vec<Rumor>,
+    /// Daily prices, oldest first. `None` when the drug wasn't on the market that day.
+    pub price_history: HashMap<Arc<Drug>, VecDeque<Option<Money>>>,
 }
 
 impl SingleLocationData {
-    pub fn get_market_element<E: ArcElement>(&self, elem: E) -> Result<&MarketElement> {
-        if TypeId::of::<E>() == TypeId::of::<Arc<Drug>>() {
-            self.drug_market
-                .get(&elem)
-                .ok_or(Error::ElementNotFound(elem.name().to_owned()))
+    pub fn get_market<E: Element>(&self) -> &HashMap<Arc<E>, MarketElement> {
+        let market: &dyn Any = if TypeId::of::<E>() == TypeId::of::<Drug>() {
+            &self.drug_market
         } else {
-            self.item_market
-                .get(&elem)
-                .ok_or(Error::ElementNotFound(elem.name().to_owned()))
-        }
+            &self.item_market
+        };
+
+        market.downcast_ref().unwrap()
     }
 
-    pub fn update_markets(&mut self, drugs: &Drugs, items: &Items, rng: &mut dyn RngCore) {
+    pub fn get_market_mut<E: Element>(&mut self) -> &mut HashMap<Arc<E>, MarketElement> {
+        let market: &mut dyn Any = if TypeId::of::<E>() == TypeId::of::<Drug>() {
+            &mut self.drug_market
+        } else {
+            &mut self.item_market
+        };
+
+        market.downcast_mut().unwrap()
+    }
+
+    pub fn get_market_element<E: Element>(&self, elem: &Arc<E>) -> Result<&MarketElement> {
+        self.get_market::<E>()
+            .get(elem)
+            .ok_or(Error::ElementNotFound(elem.name().to_owned()))
+    }
+
+    pub fn get_market_element_mut<E: Element>(
+        &mut self,
+        elem: &Arc<E>,
+    ) -> Result<&mut MarketElement> {
+        self.get_market_mut::<E>()
+            .get_mut(elem)
+            .ok_or(Error::ElementNotFound(elem.name().to_owned()))
+    }
+
+    pub fn update_markets(
+        &mut self,
+        location: &Location,
+        drugs: &Drugs,
+        items: &Items,
+        rng: &mut dyn RngCore,
+    ) {
         self.drug_market.clear();
         self.item_market.clear();
 
         for drug in drugs.iter() {
+            if !location.sells_drug(drug) {
+                continue;
+            }
+
             let mods = self
                 .price_mods
                 .clone()
@@ -76,74 +185,169 @@ impl SingleLocationData {
                 .filter(|price_mod| price_mod.drug.as_ref() == drug.as_ref())
                 .collect::<Vec<_>>();
 
-            if rng.gen_bool(4. / 5.) && mods.len() == 0 {
+            if !rng.gen_bool(location.drug_availability(drug)) && mods.is_empty() {
                 continue;
             };
 
             let supply = rng.gen_range(0..1000000);
             let demand = rng.gen_range(0..1000000);
 
-            let mut price = drug.nominal_price.clone();
+            let mut price = location.drug_price(drug);
 
             for price_mod in mods {
-                match price_mod.trend {
-                    PriceTrend::Up => price *= 15,
-                    PriceTrend::Down => price /= 6,
-                }
+                price = price.scale(price_mod.multiplier(&drug.profile));
             }
 
-            self.drug_market.insert(
-                drug.clone(),
-                MarketElement {
-                    supply,
-                    demand,
-                    price,
-                },
-            );
+            self.drug_market
+                .insert(drug.clone(), MarketElement::new(supply, demand, price));
         }
 
         for item in items.iter() {
-            if rng.gen_bool(4. / 5.) {
+            if !location.sells_item(item) || rng.gen_bool(4. / 5.) {
                 continue;
             };
 
             let supply = rng.gen_range(0..1000000);
             let demand = rng.gen_range(0..1000000);
 
-            let price = item.nominal_price.clone();
+            let price = location.item_price(item);
+
+            self.item_market
+                .insert(item.clone(), MarketElement::new(supply, demand, price));
+        }
+    }
+
+    pub fn record_prices(&mut self, drugs: &Drugs) {
+        for drug in drugs.iter() {
+            let price = self
+                .drug_market
+                .get(drug)
+                .map(|market_elem| market_elem.price.clone());
+
+            let history = self.price_history.entry(drug.clone()).or_default();
+            history.push_back(price);
+
+            while history.len() > PRICE_HISTORY_DAYS {
+                history.pop_front();
+            }
+        }
+    }
+
+    pub fn generate_news(&mut self, messages: &Messages, mut rng: &mut dyn RngCore) {
+        let yesterday = std::mem::take(&mut self.news);
+        self.news_archive.push_front(yesterday);
+        self.news_archive.truncate(NEWS_ARCHIVE_DAYS);
+
+        for price_mod in &self.price_mods {
+            if let PriceModKind::Event { name, origin } = &price_mod.kind {
+                let (color, direction) = match price_mod.trend {
+                    PriceTrend::Up => (Color::Green, "up"),
+                    PriceTrend::Down => (Color::Orange, "down"),
+                };
+
+                let msg = Msg::new()
+                    .color(color)
+                    .text(format!("{} in ", name))
+                    .color(Color::Purple)
+                    .text(origin)
+                    .color(color)
+                    .text(": ")
+                    .color(Color::Yellow)
+                    .text(&price_mod.drug.name)
+                    .color(color)
+                    .text(format!(
+                        " prices are {} (day {}/{})",
+                        direction,
+                        price_mod.age + 1,
+                        price_mod.duration
+                    ))
+                    .reset();
+
+                self.news.push(msg.to_string());
+                continue;
+            }
 
-            self.item_market.insert(
-                item.clone(),
-                MarketElement {
-                    supply,
-                    demand,
-                    price,
-                },
-            );
+            match price_mod.trend {
+                PriceTrend::Up => {
+                    let mut message = messages
+                        .get("price_up")
+                        .unwrap()
+                        .choose(&mut rng)
+                        .unwrap()
+                        .to_owned()
+                        + " "
+                        + messages
+                            .get("price_up_end")
+                            .unwrap()
+                            .choose(&mut rng)
+                            .unwrap()
+                            .as_str();
+
+                    let colored_drug = Msg::new()
+                        .color(Color::Yellow)
+                        .text(&price_mod.drug.name)
+                        .color(Color::Green);
+
+                    message = message.replace("%DRUG", &colored_drug.to_string());
+
+                    let msg = Msg::new().color(Color::Green).text(&message).reset();
+                    self.news.push(msg.to_string());
+                }
+
+                PriceTrend::Down => {
+                    let mut message = messages
+                        .get("price_down")
+                        .unwrap()
+                        .choose(&mut rng)
+                        .unwrap()
+                        .to_owned()
+                        + " "
+                        + messages
+                            .get("price_down_end")
+                            .unwrap()
+                            .choose(&mut rng)
+                            .unwrap()
+                            .as_str();
+
+                    let colored_drug = Msg::new()
+                        .color(Color::Yellow)
+                        .text(&price_mod.drug.name)
+                        .color(Color::Orange);
+
+                    message = message.replace("%DRUG", &colored_drug.to_string());
+
+                    let msg = Msg::new().color(Color::Orange).text(&message).reset();
+                    self.news.push(msg.to_string());
+                }
+            };
         }
     }
 
     pub fn update_price_mods(&mut self, drugs: &Drugs, rng: &mut dyn RngCore) {
-        self.price_mods.clear();
+        for price_mod in &mut self.price_mods {
+            price_mod.age += 1;
+        }
+        self.price_mods
+            .retain(|price_mod| price_mod.age < price_mod.duration);
 
         for drug in drugs.iter() {
-            if rng.gen_bool(0.92) {
+            if !rng.gen_bool(drug.profile.volatility) {
                 continue;
             }
 
             match rng.gen_bool(1. / 2.) {
                 // Price down
-                true => self.price_mods.push(PriceMod {
-                    drug: drug.clone(),
-                    trend: PriceTrend::Down,
-                    kind: PriceModKind::Spontaneous,
-                }),
+                true => self.price_mods.push(PriceMod::new(
+                    drug.clone(),
+                    PriceTrend::Down,
+                    PriceModKind::Spontaneous,
+                )),
                 // Price UP !
-                false => self.price_mods.push(PriceMod {
-                    drug: drug.clone(),
-                    trend: PriceTrend::Up,
-                    kind: PriceModKind::Spontaneous,
-                }),
+                false => self.price_mods.push(PriceMod::new(
+                    drug.clone(),
+                    PriceTrend::Up,
+                    PriceModKind::Spontaneous,
+                )),
             }
         }
     }
@@ -154,10 +358,11 @@ impl SingleLocationData {
         locations: &Locations,
         mut rng: &mut dyn RngCore,
     ) {
-        self.rumors.clear();
+        // yesterday's rumors stay around so the market can tell how they turned out
+        self.rumors.retain(|rumor| rumor.confirmed.is_some());
 
         for drug in drugs.iter() {
-            if rng.gen_bool(0.95) {
+            if !rng.gen_bool(drug.profile.rumor_frequency) {
                 continue;
             }
 
@@ -180,9 +385,13 @@ impl SingleLocationData {
         }
     }
 
-    pub fn confirm_rumors(&mut self, rng: &mut dyn RngCore) {
+    /// Settles the rumors heard here. The price mods of the confirmed ones
+    /// are returned along with the location they have to land on.
+    pub fn confirm_rumors(&mut self, rng: &mut dyn RngCore) -> Vec<(Arc<Location>, PriceMod)> {
         self.rumors.retain(|rumor| rumor.confirmed.is_none());
 
+        let mut confirmed = vec![];
+
         for rumor in &mut self.rumors {
             if rng.gen_bool(1. / 2.) {
                 rumor.confirmed = Some(false);
@@ -191,19 +400,20 @@ impl SingleLocationData {
 
             rumor.confirmed = Some(true);
 
-            self.price_mods.push(PriceMod {
-                drug: rumor.drug.clone(),
-                trend: rumor.trend.clone(),
-                kind: PriceModKind::Rumor,
-            });
+            confirmed.push((
+                rumor.location.clone(),
+                PriceMod::new(rumor.drug.clone(), rumor.trend.clone(), PriceModKind::Rumor),
+            ));
         }
+
+        confirmed
     }
 }
 
 #[derive(Default)]
-pub struct LocationData(pub HashMap<Arc<Location>, Arc<RwLock<SingleLocationData>>>);
+pub struct LocationData(pub HashMap<Arc<Location>, SingleLocationData>);
 impl Deref for LocationData {
-    type Target = HashMap<Arc<Location>, Arc<RwLock<SingleLocationData>>>;
+    type Target = HashMap<Arc<Location>, SingleLocationData>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -216,14 +426,136 @@ impl DerefMut for LocationData {
 }
 
 impl LocationData {
+    /// Rolls a new day for every location. Locations are always gone through
+    /// in the configured order, never the map's, so a seeded generator gives
+    /// the same day.
     pub fn update(&mut self, game_data: &GameData, rng: &mut dyn RngCore) {
-        for data_arc in self.values_mut() {
-            let mut data = data_arc.write().unwrap();
-
+        for location in game_data.locations.iter() {
+            let data = self.get_mut(location).unwrap();
             data.update_price_mods(&game_data.drugs, rng);
-            data.confirm_rumors(rng);
-            data.update_markets(&game_data.drugs, &game_data.items, rng);
+        }
+
+        self.start_events(game_data, rng);
+        self.confirm_rumors(&game_data.locations, rng);
+
+        for location in game_data.locations.iter() {
+            let data = self.get_mut(location).unwrap();
+
+            data.update_markets(location, &game_data.drugs, &game_data.items, rng);
+            data.record_prices(&game_data.drugs);
+            data.generate_news(&game_data.messages, rng);
             data.generate_rumors(&game_data.drugs, &game_data.locations, rng)
         }
     }
+
+    /// Rolls the configured market events for every location. An event hits
+    /// its origin at full strength and every location within its spread
+    /// radius with a strength fading with the distance.
+    fn start_events(&mut self, game_data: &GameData, mut rng: &mut dyn RngCore) {
+        let mut started = vec![];
+
+        for origin in game_data.locations.iter() {
+            for event in game_data.events.iter() {
+                if !rng.gen_bool(event.chance) {
+                    continue;
+                }
+
+                let drug = match event.drugs.iter().choose(&mut rng) {
+                    Some(drug) => drug.clone(),
+                    None => game_data.drugs.iter().choose(&mut rng).unwrap().clone(),
+                };
+
+                started.push((origin.clone(), event.clone(), drug));
+            }
+        }
+
+        for (origin, event, drug) in started {
+            for location in game_data.locations.iter() {
+                let data = self.get_mut(location).unwrap();
+                let strength = match location == &origin {
+                    true => 1.,
+                    // without a spread the event stays at its origin
+                    false if event.spread <= 0. => continue,
+                    false => 1. - (get_distance(&origin, location) / event.spread) as f64,
+                };
+
+                if strength <= 0. {
+                    continue;
+                }
+
+                data.price_mods.push(PriceMod {
+                    drug: drug.clone(),
+                    trend: event.trend.clone(),
+                    kind: PriceModKind::Event {
+                        name: event.name.clone(),
+                        origin: origin.name.clone(),
+                    },
+                    duration: event.duration,
+                    age: 0,
+                    decay: event.decay,
+                    strength,
+                });
+            }
+        }
+    }
+
+    fn confirm_rumors(&mut self, locations: &Locations, rng: &mut dyn RngCore) {
+        let mut confirmed = vec![];
+
+        for location in locations.iter() {
+            let data = self.get_mut(location).unwrap();
+            confirmed.extend(data.confirm_rumors(rng));
+        }
+
+        for (location, price_mod) in confirmed {
+            if let Some(data) = self.get_mut(&location) {
+                data.price_mods.push(price_mod);
+            }
+        }
+    }
+
+    /// Drugs whose price moved the most between yesterday and today.
+    pub fn price_movers(&self, count: usize) -> Vec<PriceMove> {
+        let mut moves = vec![];
+
+        for (location, data) in self.iter() {
+            for (drug, history) in data.price_history.iter() {
+                let mut last_days = history.iter().rev();
+
+                if let (Some(Some(to)), Some(Some(from))) = (last_days.next(), last_days.next()) {
+                    moves.push(PriceMove {
+                        location: location.clone(),
+                        drug: drug.clone(),
+                        from: from.clone(),
+                        to: to.clone(),
+                    });
+                }
+            }
+        }
+
+        moves.sort_by(|a, b| b.change().abs().total_cmp(&a.change().abs()));
+        moves.truncate(count);
+        moves
+    }
+
+    /// Rumors that got confirmed or debunked today, heard in any location.
+    pub fn settled_rumors(&self) -> Vec<Rumor> {
+        let mut rumors = vec![];
+
+        for data in self.values() {
+            for rumor in data.rumors.iter() {
+                let already_known = rumors.iter().any(|known: &Rumor| {
+                    known.drug == rumor.drug
+                        && known.location == rumor.location
+                        && known.trend == rumor.trend
+                });
+
+                if rumor.confirmed.is_some() && !already_known {
+                    rumors.push(rumor.clone());
+                }
+            }
+        }
+
+        rumors
+    }
 }
@@ -0,0 +1,236 @@
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use prometheus::{
+    Encoder, Gauge, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec,
+    Opts, Registry, TextEncoder,
+};
+
+use crate::{dealer::DealerStatus, money::Money, state::GameState};
+
+/// How long a dealer counts as active after doing something.
+const ACTIVE_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Health and economy stats, scraped in Prometheus text format.
+///
+/// The game records into them and the API listener reads them from its own
+/// task, so they live in a static.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub struct Metrics {
+    registry: Registry,
+    commands: IntCounterVec,
+    commands_per_minute: IntGaugeVec,
+    command_duration: HistogramVec,
+    rollover_duration: Histogram,
+    dealers: IntGauge,
+    active_dealers: IntGauge,
+    dirty_money: Gauge,
+    laundered_money: Gauge,
+    flights: IntGauge,
+    /// Commands run in the last minute, oldest first.
+    recent_commands: Mutex<VecDeque<(Instant, &'static str)>>,
+    /// Every command ever run, so idle ones read 0 per minute.
+    command_names: Mutex<BTreeSet<&'static str>>,
+    /// When each dealer last did something that got logged.
+    last_seen: Mutex<HashMap<String, Instant>>,
+}
+
+/// Times a command until it's dropped.
+pub struct CommandTimer {
+    name: &'static str,
+    started: Instant,
+}
+
+impl Drop for CommandTimer {
+    fn drop(&mut self) {
+        METRICS.finish_command(self.name, self.started.elapsed());
+    }
+}
+
+fn forget_old_commands(recent: &mut VecDeque<(Instant, &'static str)>) {
+    while recent
+        .front()
+        .is_some_and(|(at, _)| at.elapsed() > Duration::from_secs(60))
+    {
+        recent.pop_front();
+    }
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("drugwars".to_owned()), None).unwrap();
+
+        let commands = IntCounterVec::new(
+            Opts::new("commands_total", "Commands run, by name"),
+            &["command"],
+        )
+        .unwrap();
+        let commands_per_minute = IntGaugeVec::new(
+            Opts::new(
+                "commands_per_minute",
+                "Commands run in the last minute, by name",
+            ),
+            &["command"],
+        )
+        .unwrap();
+        let command_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "command_duration_seconds",
+                "How long commands take, by name",
+            )
+            .buckets(vec![
+                0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25,
+            ]),
+            &["command"],
+        )
+        .unwrap();
+        let rollover_duration = Histogram::with_opts(
+            HistogramOpts::new(
+                "rollover_duration_seconds",
+                "How long rolling the days over takes",
+            )
+            .buckets(vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1., 5.]),
+        )
+        .unwrap();
+        let dealers = IntGauge::new("dealers", "Registered dealers").unwrap();
+        let active_dealers = IntGauge::new(
+            "active_dealers",
+            "Living dealers who did something in the last 24 hours",
+        )
+        .unwrap();
+        let dirty_money =
+            Gauge::new("dirty_money_dollars", "Dirty money held by all dealers").unwrap();
+        let laundered_money =
+            Gauge::new("laundered_money_dollars", "Money laundered by all dealers").unwrap();
+        let flights = IntGauge::new("flights", "Flights in progress").unwrap();
+
+        registry.register(Box::new(commands.clone())).unwrap();
+        registry
+            .register(Box::new(commands_per_minute.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(command_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(rollover_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(dealers.clone())).unwrap();
+        registry.register(Box::new(active_dealers.clone())).unwrap();
+        registry.register(Box::new(dirty_money.clone())).unwrap();
+        registry
+            .register(Box::new(laundered_money.clone()))
+            .unwrap();
+        registry.register(Box::new(flights.clone())).unwrap();
+
+        Self {
+            registry,
+            commands,
+            commands_per_minute,
+            command_duration,
+            rollover_duration,
+            dealers,
+            active_dealers,
+            dirty_money,
+            laundered_money,
+            flights,
+            recent_commands: Mutex::default(),
+            command_names: Mutex::default(),
+            last_seen: Mutex::default(),
+        }
+    }
+
+    /// Starts timing a command, it's counted when the timer drops.
+    pub fn command(&self, name: &'static str) -> CommandTimer {
+        CommandTimer {
+            name,
+            started: Instant::now(),
+        }
+    }
+
+    fn finish_command(&self, name: &'static str, duration: Duration) {
+        self.commands.with_label_values(&[name]).inc();
+        self.command_duration
+            .with_label_values(&[name])
+            .observe(duration.as_secs_f64());
+
+        let mut recent = self.recent_commands.lock().unwrap();
+        recent.push_back((Instant::now(), name));
+        forget_old_commands(&mut recent);
+        drop(recent);
+
+        self.command_names.lock().unwrap().insert(name);
+    }
+
+    pub fn observe_rollover(&self, duration: Duration) {
+        self.rollover_duration.observe(duration.as_secs_f64());
+    }
+
+    pub fn saw_dealer(&self, nick: &str) {
+        self.last_seen
+            .lock()
+            .unwrap()
+            .insert(nick.to_owned(), Instant::now());
+    }
+
+    /// Takes the economy stats from the game as it is now.
+    pub fn update_game(&self, state: &GameState) {
+        let last_seen = self.last_seen.lock().unwrap();
+
+        let active = state
+            .dealers
+            .values()
+            .filter(|dealer| !matches!(dealer.status, DealerStatus::Dead(_)))
+            .filter(|dealer| {
+                last_seen
+                    .get(&dealer.nick)
+                    .is_some_and(|seen| seen.elapsed() < ACTIVE_WINDOW)
+            })
+            .count();
+
+        self.dealers.set(state.dealers.len() as i64);
+        self.active_dealers.set(active as i64);
+        self.dirty_money.set(
+            state
+                .dealers
+                .values()
+                .map(|dealer| &dealer.money)
+                .sum::<Money>()
+                .to_dollars(),
+        );
+        self.laundered_money.set(
+            state
+                .dealers
+                .values()
+                .map(|dealer| &dealer.laundered_money)
+                .sum::<Money>()
+                .to_dollars(),
+        );
+        self.flights.set(state.flights.len() as i64);
+    }
+
+    /// Every metric in Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut recent = self.recent_commands.lock().unwrap();
+        forget_old_commands(&mut recent);
+
+        for name in self.command_names.lock().unwrap().iter() {
+            let count = recent.iter().filter(|(_, command)| command == name).count();
+            self.commands_per_minute
+                .with_label_values(&[name])
+                .set(count as i64);
+        }
+
+        drop(recent);
+
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+
+        String::from_utf8(buffer).unwrap()
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    iter::Sum,
+    ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign},
+    str::FromStr,
+};
+
+use num_bigint::{BigInt, BigUint, Sign, ToBigInt};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    error::{Error, Result},
+    utils::{parse_number, PrettyMoney},
+};
+
+/// Money is stored in ten-thousandths of a dollar.
+const SCALE: i64 = 10000;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Money(BigInt);
+
+impl Money {
+    pub fn from_dollars(dollars: f64) -> Self {
+        Self(
+            (dollars * SCALE as f64)
+                .round()
+                .to_bigint()
+                .unwrap_or_default(),
+        )
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.0.sign() == Sign::Plus
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0.sign() == Sign::Minus
+    }
+
+    /// Multiplies by a factor, keeping four decimals of it.
+    pub fn scale(&self, multiplier: f64) -> Self {
+        Self(
+            &self.0
+                * (multiplier * SCALE as f64)
+                    .round()
+                    .to_bigint()
+                    .unwrap_or_default()
+                / SCALE,
+        )
+    }
+
+    pub fn percent(&self, percent: f64) -> Self {
+        self.scale(percent / 100.)
+    }
+
+    /// The amount in dollars, as close as a float gets. Only for stats.
+    pub fn to_dollars(&self) -> f64 {
+        self.0.to_string().parse::<f64>().unwrap_or_default() / SCALE as f64
+    }
+
+    /// How many times `other` fits in this amount, zero when `other` is.
+    pub fn ratio(&self, other: &Money) -> f64 {
+        if other.0.sign() == Sign::NoSign {
+            return 0.;
+        }
+
+        let ratio = &self.0 * SCALE / &other.0;
+
+        i64::try_from(&ratio).unwrap_or(if ratio.sign() == Sign::Minus {
+            i64::MIN
+        } else {
+            i64::MAX
+        }) as f64
+            / SCALE as f64
+    }
+}
+
+/// Serializes money as an exact decimal string like `"-1234.5"` rather than
+/// raw big integer digits, for files meant to be read by people and tools.
+pub mod decimal {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        money: &Money,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        let magnitude = money.0.magnitude();
+        let fraction = (magnitude % SCALE as u64).to_string();
+        let fraction = format!("{:0>4}", fraction);
+        let fraction = fraction.trim_end_matches('0');
+
+        let sign = match money.is_negative() {
+            true => "-",
+            false => "",
+        };
+
+        let dollars = magnitude / SCALE as u64;
+
+        serializer.serialize_str(&match fraction.is_empty() {
+            true => format!("{}{}", sign, dollars),
+            false => format!("{}{}.{}", sign, dollars, fraction),
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Money, D::Error> {
+        let val = String::deserialize(deserializer)?;
+        let invalid = || de::Error::custom(format!("invalid amount of money {:?}", val));
+
+        let (negative, digits) = match val.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, val.as_str()),
+        };
+        let (dollars, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+        if dollars.is_empty()
+            || fraction.len() > 4
+            || !dollars
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+
+        let units = format!("{}{:0<4}", dollars, fraction)
+            .parse::<BigInt>()
+            .map_err(|_| invalid())?;
+
+        Ok(Money(match negative {
+            true => -units,
+            false => units,
+        }))
+    }
+}
+
+/// Reads `1000`, `$1,000`, `1.5k` or `$2m`.
+impl FromStr for Money {
+    type Err = Error;
+
+    fn from_str(val: &str) -> Result<Self> {
+        parse_number(&val.trim_start_matches('$').to_lowercase())
+            .map(Money::from_dollars)
+            .ok_or(Error::InvalidMoney(val.to_owned()))
+    }
+}
+
+impl PrettyMoney for Money {
+    fn pretty_money(&self) -> String {
+        let magnitude = self.0.magnitude();
+        let cents = magnitude / (SCALE as u64 / 100);
+
+        // Don't show -$0.00 for amounts that round to nothing.
+        let sign = match self.is_negative() && cents > BigUint::default() {
+            true => "-",
+            false => "",
+        };
+
+        let dollars = (&cents / 100u64)
+            .to_string()
+            .as_bytes()
+            .rchunks(3)
+            .rev()
+            .map(std::str::from_utf8)
+            .collect::<std::result::Result<Vec<&str>, _>>()
+            .unwrap()
+            .join(",");
+
+        format!("{}${}.{:0>2}", sign, dollars, cents % 100u64)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money(self.0 + other.0)
+    }
+}
+
+impl Add<&Money> for Money {
+    type Output = Money;
+
+    fn add(self, other: &Money) -> Money {
+        Money(self.0 + &other.0)
+    }
+}
+
+impl Add<&Money> for &Money {
+    type Output = Money;
+
+    fn add(self, other: &Money) -> Money {
+        Money(&self.0 + &other.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        Money(self.0 - other.0)
+    }
+}
+
+impl Sub<&Money> for Money {
+    type Output = Money;
+
+    fn sub(self, other: &Money) -> Money {
+        Money(self.0 - &other.0)
+    }
+}
+
+impl Sub<&Money> for &Money {
+    type Output = Money;
+
+    fn sub(self, other: &Money) -> Money {
+        Money(&self.0 - &other.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        self.0 += other.0;
+    }
+}
+
+impl AddAssign<&Money> for Money {
+    fn add_assign(&mut self, other: &Money) {
+        self.0 += &other.0;
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, other: Money) {
+        self.0 -= other.0;
+    }
+}
+
+impl SubAssign<&Money> for Money {
+    fn sub_assign(&mut self, other: &Money) {
+        self.0 -= &other.0;
+    }
+}
+
+impl Mul<usize> for Money {
+    type Output = Money;
+
+    fn mul(self, amount: usize) -> Money {
+        Money(self.0 * amount)
+    }
+}
+
+impl Mul<usize> for &Money {
+    type Output = Money;
+
+    fn mul(self, amount: usize) -> Money {
+        Money(&self.0 * amount)
+    }
+}
+
+impl Div<usize> for Money {
+    type Output = Money;
+
+    fn div(self, amount: usize) -> Money {
+        Money(self.0 / amount)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::default(), |total, money| total + money)
+    }
+}
+
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item = &'a Money>>(iter: I) -> Money {
+        iter.fold(Money::default(), |total, money| total + money)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Wrapped(#[serde(with = "decimal")] Money);
+
+    #[test]
+    fn negative_money_is_pretty() {
+        assert_eq!(Money::from_dollars(-1234.56).pretty_money(), "-$1,234.56");
+        assert_eq!(Money::from_dollars(1234.56).pretty_money(), "$1,234.56");
+        assert_eq!(
+            Money::from_dollars(-1234567.8).pretty_money(),
+            "-$1,234,567.80"
+        );
+    }
+
+    #[test]
+    fn nothing_isnt_negative() {
+        assert_eq!(Money::from_dollars(-0.004).pretty_money(), "$0.00");
+        assert_eq!(Money::from_dollars(-0.0001).pretty_money(), "$0.00");
+        assert_eq!(Money::from_dollars(-0.01).pretty_money(), "-$0.01");
+    }
+
+    #[test]
+    fn decimals_round_trip() {
+        for (dollars, decimal) in [
+            (-1234.5, "\"-1234.5\""),
+            (-0.0001, "\"-0.0001\""),
+            (0.25, "\"0.25\""),
+            (-7., "\"-7\""),
+        ] {
+            let money = Wrapped(Money::from_dollars(dollars));
+            let json = serde_json::to_string(&money).unwrap();
+
+            assert_eq!(json, decimal);
+            assert_eq!(serde_json::from_str::<Wrapped>(&json).unwrap(), money);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Settings,
+    error::{Error, Result},
+    frontend::Route,
+};
+
+/// Narrowest render a dealer can ask for, boxes don't fit below that.
+pub const MIN_WIDTH: usize = 50;
+pub const MAX_WIDTH: usize = 300;
+
+/// How market tables are ordered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketSort {
+    #[default]
+    Name,
+    /// Cheapest first.
+    Price,
+    /// Most on the market first.
+    Supply,
+    /// Most wanted first.
+    Demand,
+}
+
+/// How a dealer likes the game to talk to them, kept in the save.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// Where tables and boxes go, the game's `bulky_output` when unset.
+    pub output: Option<Route>,
+    /// Render width, the game's `width` when unset.
+    pub width: Option<usize>,
+    pub colour: bool,
+    /// Plain lines instead of boxes.
+    pub compact: bool,
+    pub sort: MarketSort,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            output: None,
+            width: None,
+            colour: true,
+            compact: false,
+            sort: MarketSort::default(),
+        }
+    }
+}
+
+/// What a render needs to know about whoever reads it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct View {
+    pub width: usize,
+    pub compact: bool,
+    pub sort: MarketSort,
+}
+
+fn parse_switch(value: &str) -> Result<bool> {
+    match value.to_lowercase().as_str() {
+        "on" | "yes" | "true" => Ok(true),
+        "off" | "no" | "false" => Ok(false),
+        _ => Err(Error::InvalidSwitch(value.to_owned())),
+    }
+}
+
+fn parse_width(value: &str) -> Result<usize> {
+    match value.parse::<usize>() {
+        Ok(width) if (MIN_WIDTH..=MAX_WIDTH).contains(&width) => Ok(width),
+        _ => Err(Error::InvalidWidth(value.to_owned(), MIN_WIDTH, MAX_WIDTH)),
+    }
+}
+
+fn parse_sort(value: &str) -> Result<MarketSort> {
+    match value.to_lowercase().as_str() {
+        "name" => Ok(MarketSort::Name),
+        "price" => Ok(MarketSort::Price),
+        "supply" => Ok(MarketSort::Supply),
+        "demand" => Ok(MarketSort::Demand),
+        _ => Err(Error::InvalidSort(value.to_owned())),
+    }
+}
+
+impl Preferences {
+    /// Changes one setting by name, `default` puts it back to the game's.
+    /// Returns the setting's proper name.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<&'static str> {
+        let unset = value.eq_ignore_ascii_case("default");
+        let defaults = Self::default();
+
+        let name = match name.to_lowercase().as_str() {
+            "output" => {
+                self.output = match unset {
+                    true => None,
+                    false => Some(value.parse()?),
+                };
+                "output"
+            }
+            "width" => {
+                self.width = match unset {
+                    true => None,
+                    false => Some(parse_width(value)?),
+                };
+                "width"
+            }
+            "colour" | "color" => {
+                self.colour = match unset {
+                    true => defaults.colour,
+                    false => parse_switch(value)?,
+                };
+                "colour"
+            }
+            "compact" => {
+                self.compact = match unset {
+                    true => defaults.compact,
+                    false => parse_switch(value)?,
+                };
+                "compact"
+            }
+            "sort" => {
+                self.sort = match unset {
+                    true => defaults.sort,
+                    false => parse_sort(value)?,
+                };
+                "sort"
+            }
+            _ => return Err(Error::UnknownPreference(name.to_owned())),
+        };
+
+        Ok(name)
+    }
+
+    /// Each setting and its value, the game's default shown as such.
+    pub fn describe(&self) -> Vec<(&'static str, String)> {
+        let or_default = |value: Option<String>| value.unwrap_or_else(|| "default".to_owned());
+        let switch = |on: bool| match on {
+            true => "on".to_owned(),
+            false => "off".to_owned(),
+        };
+
+        vec![
+            (
+                "output",
+                or_default(self.output.map(|route| route.to_string())),
+            ),
+            (
+                "width",
+                or_default(self.width.map(|width| width.to_string())),
+            ),
+            ("colour", switch(self.colour)),
+            ("compact", switch(self.compact)),
+            ("sort", format!("{:?}", self.sort).to_lowercase()),
+        ]
+    }
+
+    pub fn view(&self, settings: &Settings) -> View {
+        View {
+            width: self.width.unwrap_or(settings.width),
+            compact: self.compact,
+            sort: self.sort,
+        }
+    }
+}
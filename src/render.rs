@@ -1,40 +1,120 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+use chrono::Duration;
 use ircie::format::{Color, Msg};
 use itertools::Itertools;
-use rand::{seq::SliceRandom, RngCore};
 
 use crate::{
+    config::Settings,
+    customs::cargo_amount,
     dealer::Dealer,
-    location_data::{PriceTrend, SingleLocationData},
+    element::{Element, MarketElement},
+    event_log::LogEntry,
+    location_data::{PriceMove, PriceTrend, Rumor, SingleLocationData},
+    money::Money,
+    preferences::{MarketSort, View},
     renderer::{RenderBox, RenderBoxContent, Renderer},
-    resources::{Location, Locations, Messages},
-    utils::{get_flight_price, PrettyAmount, PrettyMoney, StringManips},
+    resources::{Drug, Flight, Item, Location, Locations, Routes, TravelMode, TravelModes},
+    utils::{get_route_days, get_route_price, PrettyAmount, PrettyMoney, StringManips},
 };
 
-pub fn render_info(dealer: &Dealer) -> Vec<String> {
-    Renderer::new(50)
+/// Narrowest the market boxes fit in, it comes out compact below that.
+const MARKET_MIN_WIDTH: usize = 110;
+
+/// A renderer for boxes `width` wide, compact when the reader asked for it
+/// or can't fit them.
+fn renderer<'a>(view: &View, width: usize) -> Renderer<'a> {
+    let mut renderer = Renderer::new(width);
+    renderer.compact(view.compact || width > view.width);
+    renderer
+}
+
+fn sorted_market<E: Element>(
+    market: &HashMap<Arc<E>, MarketElement>,
+    sort: MarketSort,
+    day_duration: u32,
+) -> Vec<(&Arc<E>, &MarketElement)> {
+    market
+        .iter()
+        .sorted_by(|(a, a_market), (b, b_market)| {
+            match sort {
+                MarketSort::Name => a.name().cmp(b.name()),
+                MarketSort::Price => a_market
+                    .current_price(day_duration)
+                    .cmp(&b_market.current_price(day_duration)),
+                MarketSort::Supply => b_market.supply.cmp(&a_market.supply),
+                MarketSort::Demand => b_market.demand.cmp(&a_market.demand),
+            }
+            .then_with(|| a.name().cmp(b.name()))
+        })
+        .collect()
+}
+
+pub fn render_info(view: &View, dealer: &Dealer, flight: Option<&Flight>) -> Vec<String> {
+    let mut info_content = RenderBoxContent::new();
+
+    info_content
+        .sizes([18, 25])
+        .add_row(["nick".to_owned(), dealer.nick.to_owned()])
+        .add_row(["health".to_owned(), format!("{:.2} hp", dealer.health)])
+        .add_row(["dirty money".to_owned(), dealer.money.pretty_money()])
+        .add_row([
+            "money laundered".to_owned(),
+            dealer.laundered_money.pretty_money(),
+        ])
+        .add_row(["location".to_owned(), dealer.location.name.clone()])
+        .add_row(["capacity".to_owned(), dealer.capacity.pretty_amount()])
+        .add_row(["status".to_owned(), dealer.status.pretty()]);
+
+    let carried =
+        cargo_amount(&dealer.carry_on) + flight.map_or(0, |flight| cargo_amount(&flight.cargo));
+
+    if carried > 0 {
+        info_content.add_row(["carry-on".to_owned(), carried.pretty_amount()]);
+    }
+
+    if let Some(hold) = &dealer.customs_hold {
+        info_content.add_row([
+            "held by customs".to_owned(),
+            format!(
+                "{} ({})",
+                cargo_amount(&hold.cargo).pretty_amount(),
+                hold.bribe.pretty_money()
+            ),
+        ]);
+    }
+
+    if let Some(flight) = flight {
+        info_content
+            .add_row(["flying to".to_owned(), flight.destination.name.clone()])
+            .add_row([
+                "arrival".to_owned(),
+                flight.arrival.format("%Y-%m-%d").to_string(),
+            ]);
+
+        if let Some((layover, day)) = flight.layovers.front() {
+            info_content.add_row([
+                "next stop".to_owned(),
+                format!("{} ({})", layover.name, day.format("%m-%d")),
+            ]);
+        }
+    }
+
+    renderer(view, 50)
         .add_box(
             &RenderBox::new()
                 .headers(["Dealer Info".to_owned()])
-                .add_content([&RenderBoxContent::new()
-                    .sizes([18, 25])
-                    .add_row(["nick".to_owned(), dealer.nick.to_owned()])
-                    .add_row(["health".to_owned(), format!("{:.2} hp", dealer.health)])
-                    .add_row(["dirty money".to_owned(), dealer.money.pretty_money()])
-                    .add_row([
-                        "money laundered".to_owned(),
-                        dealer.laundered_money.pretty_money(),
-                    ])
-                    .add_row(["location".to_owned(), dealer.location.name.clone()])
-                    .add_row(["capacity".to_owned(), dealer.capacity.pretty_amount()])
-                    .add_row(["status".to_owned(), dealer.status.pretty()])
-                    .get()])
+                .add_content([&info_content.get()])
                 .get(),
         )
         .build()
 }
 
-pub fn render_help() -> Vec<String> {
-    Renderer::new(90)
+pub fn render_help(view: &View) -> Vec<String> {
+    renderer(view, 90)
         .add_box(
             &RenderBox::new()
                 .headers(["Command list".to_owned()])
@@ -49,6 +129,18 @@ pub fn render_help() -> Vec<String> {
                         "show the people at your location".to_owned(),
                     ])
                     .add_row(["t".to_owned(), "print the date and time".to_owned()])
+                    .add_row([
+                        "set [setting] [value]".to_owned(),
+                        "show or change your settings".to_owned(),
+                    ])
+                    .add_row([
+                        "news [days ago]".to_owned(),
+                        "show the headlines from every location".to_owned(),
+                    ])
+                    .add_row([
+                        "ph <drug> [location]".to_owned(),
+                        "show the drug price history".to_owned(),
+                    ])
                     .add_row([
                         "a <target> <weapon>".to_owned(),
                         "attack someone".to_owned(),
@@ -69,30 +161,61 @@ pub fn render_help() -> Vec<String> {
                     ])
                     .add_row(["st <amount>".to_owned(), "sell thugs".to_owned()])
                     .add_row([
-                        "bd <drug> <amount>".to_owned(),
+                        "bd <drug> <amount> [max price]".to_owned(),
                         "buy drug from market".to_owned(),
                     ])
                     .add_row([
-                        "sd <drug> <amount>".to_owned(),
+                        "sd <drug> <amount> [min price]".to_owned(),
                         "sell drug to market".to_owned(),
                     ])
                     .add_row([
-                        "bi <drug> <amount>".to_owned(),
+                        "bi <item> <amount> [max price]".to_owned(),
                         "buy item from market".to_owned(),
                     ])
                     .add_row([
-                        "si <drug> <amount>".to_owned(),
+                        "si <item> <amount> [min price]".to_owned(),
                         "sell item to market".to_owned(),
                     ])
+                    .add_row([
+                        "cbd/csd <drug> <amount>".to_owned(),
+                        "check the average price to buy/sell drug".to_owned(),
+                    ])
+                    .add_row([
+                        "cbi/csi <item> <amount>".to_owned(),
+                        "check the average price to buy/sell item".to_owned(),
+                    ])
                     .add_row(["bc <amount>".to_owned(), "buy inventory slots".to_owned()])
                     .add_row([
                         "cc <amount>".to_owned(),
                         "check price to add <amount> inventory slots".to_owned(),
                     ])
-                    .add_row(["cf ".to_owned(), "check flight prices".to_owned()])
                     .add_row([
-                        "f <destination>".to_owned(),
-                        "fly to destination".to_owned(),
+                        "cf [mode]".to_owned(),
+                        "check travel prices, by plane by default".to_owned(),
+                    ])
+                    .add_row([
+                        "cf <destination>".to_owned(),
+                        "compare travel modes to destination".to_owned(),
+                    ])
+                    .add_row([
+                        "f <destination> [mode]".to_owned(),
+                        "travel to destination, by plane by default".to_owned(),
+                    ])
+                    .add_row([
+                        "stay".to_owned(),
+                        "end your trip at the current layover".to_owned(),
+                    ])
+                    .add_row([
+                        "pack <drug> <amount>".to_owned(),
+                        "take drugs along on your next trip".to_owned(),
+                    ])
+                    .add_row([
+                        "unpack <drug> <amount>".to_owned(),
+                        "put packed drugs back in the local stash".to_owned(),
+                    ])
+                    .add_row([
+                        "bribe".to_owned(),
+                        "get your carry-on back from customs".to_owned(),
                     ])
                     .add_row([
                         "cshd <drug> <amount> <destination>".to_owned(),
@@ -122,19 +245,31 @@ pub fn render_help() -> Vec<String> {
                         "gi <bloke> <item> <amount>".to_owned(),
                         "give items to some bloke".to_owned(),
                     ])
+                    .add_row([
+                        "<amount>".to_owned(),
+                        "a number, 1.5k, 50%, all, max or $1,000 worth".to_owned(),
+                    ])
                     .get()])
                 .get(),
         )
         .build()
 }
 
-pub fn render_admin_help() -> Vec<String> {
-    Renderer::new(90)
+pub fn render_admin_help(view: &View) -> Vec<String> {
+    renderer(view, 90)
         .add_box(
             &RenderBox::new()
                 .headers(["Command list".to_owned()])
                 .add_content([&RenderBoxContent::new()
                     .add_row(["save".to_owned(), "save the game".to_owned()])
+                    .add_row([
+                        "events <nick> [count]".to_owned(),
+                        "show what a dealer did lately".to_owned(),
+                    ])
+                    .add_row([
+                        "rollback <date> [confirm]".to_owned(),
+                        "put the game back to the start of a day".to_owned(),
+                    ])
                     .add_row(["dealers".to_owned(), "show all dealers".to_owned()])
                     .add_row(["ff".to_owned(), "advance to next day".to_owned()])
                     .get()])
@@ -144,14 +279,17 @@ pub fn render_admin_help() -> Vec<String> {
 }
 
 pub fn render_market(
-    width: usize,
-    mut rng: &mut dyn RngCore,
+    settings: &Settings,
+    view: &View,
     nick: &str,
     dealer: &Dealer,
     location: &SingleLocationData,
-    messages: &Messages,
 ) -> Vec<String> {
-    let mut renderer = Renderer::new(width);
+    let width = view.width.max(MARKET_MIN_WIDTH);
+    let mut renderer = renderer(view, width);
+
+    // names get what's left after supply, demand and price
+    let name_width = (width / 2 - 3 - 39).min(30);
 
     let drugs_owned = dealer.owned_drugs.get(&dealer.location).unwrap();
     let items_owned = dealer.owned_items.get(&dealer.location).unwrap();
@@ -159,82 +297,53 @@ pub fn render_market(
     let mut rumor_content = RenderBoxContent::<1>::new();
 
     for rumor in &location.rumors {
-        if rumor.confirmed.is_none() {
-            let mut msg = Msg::new()
+        let mut msg = match rumor.confirmed {
+            None => Msg::new()
                 .color(Color::Cyan)
                 .text("You hear a rumor that ")
                 .color(Color::Yellow)
                 .text(&rumor.drug.name)
-                .color(Color::Cyan);
-            msg = match rumor.trend {
-                PriceTrend::Up => msg.text(" will be abundant in "),
-                PriceTrend::Down => msg.text(" will be scarce in "),
-            };
-
-            msg = msg
-                .color(Color::Purple)
-                .text(&rumor.location.name)
+                .color(Color::Cyan),
+            Some(_) => Msg::new()
                 .color(Color::Cyan)
-                .text(" tomorrow.");
-
-            rumor_content.add_row([msg.to_string()]);
-        }
-    }
-
-    for price_mod in &location.price_mods {
-        match price_mod.trend {
-            PriceTrend::Up => {
-                let mut message = messages
-                    .get("price_up")
-                    .unwrap()
-                    .choose(&mut rng)
-                    .unwrap()
-                    .to_owned()
-                    + " "
-                    + messages
-                        .get("price_up_end")
-                        .unwrap()
-                        .choose(&mut rng)
-                        .unwrap()
-                        .as_str();
-
-                let colored_drug = Msg::new()
-                    .color(Color::Yellow)
-                    .text(&price_mod.drug.name)
-                    .color(Color::Green);
+                .text("The rumor that ")
+                .color(Color::Yellow)
+                .text(&rumor.drug.name)
+                .color(Color::Cyan),
+        };
 
-                message = message.replace("%DRUG", &colored_drug.to_string());
+        msg = match (&rumor.trend, rumor.confirmed) {
+            (PriceTrend::Up, None) => msg.text(" will be scarce in "),
+            (PriceTrend::Down, None) => msg.text(" will be abundant in "),
+            (PriceTrend::Up, Some(_)) => msg.text(" would be scarce in "),
+            (PriceTrend::Down, Some(_)) => msg.text(" would be abundant in "),
+        };
 
-                let msg = Msg::new().color(Color::Green).text(&message).reset();
-                rumor_content.add_row([msg.to_string()]);
-            }
+        msg = msg.color(Color::Purple).text(&rumor.location.name);
 
-            PriceTrend::Down => {
-                let mut message = messages
-                    .get("price_down")
-                    .unwrap()
-                    .choose(&mut rng)
-                    .unwrap()
-                    .to_owned()
-                    + " "
-                    + messages
-                        .get("price_down_end")
-                        .unwrap()
-                        .choose(&mut rng)
-                        .unwrap()
-                        .as_str();
-
-                let colored_drug = Msg::new()
-                    .color(Color::Yellow)
-                    .text(&price_mod.drug.name)
-                    .color(Color::Orange);
+        msg = match rumor.confirmed {
+            None => msg.color(Color::Cyan).text(" tomorrow."),
+            Some(true) => msg
+                .color(Color::Cyan)
+                .text(" turned out ")
+                .color(Color::Green)
+                .text("true")
+                .color(Color::Cyan)
+                .text("."),
+            Some(false) => msg
+                .color(Color::Cyan)
+                .text(" turned out ")
+                .color(Color::Red)
+                .text("false")
+                .color(Color::Cyan)
+                .text("."),
+        };
 
-                message = message.replace("%DRUG", &colored_drug.to_string());
+        rumor_content.add_row([msg.reset().to_string()]);
+    }
 
-                let msg = Msg::new().color(Color::Orange).text(&message).reset();
-                rumor_content.add_row([msg.to_string()]);
-            }
-        };
+    for headline in &location.news {
+        rumor_content.add_row([headline.clone()]);
     }
     let rumor_content = rumor_content.get();
 
@@ -246,7 +355,7 @@ pub fn render_market(
             "Demand".to_owned(),
             "Price".to_owned(),
         ])
-        .sizes([18, 10, 10, 19]);
+        .sizes([name_width, 10, 10, 19]);
 
     let mut drugs_owned_content = RenderBoxContent::new();
     drugs_owned_content
@@ -255,9 +364,12 @@ pub fn render_market(
             "Amount".to_owned(),
             "Bought at".to_owned(),
         ])
-        .sizes([18, 10, 25]);
+        .sizes([name_width, 10, 25]);
 
-    for pair in location.drug_market.iter().zip_longest(drugs_owned.iter()) {
+    let drug_market = sorted_market(&location.drug_market, view.sort, settings.day_duration);
+    let drugs_owned_sorted = drugs_owned.iter().sorted_by(|a, b| a.0.name.cmp(&b.0.name));
+
+    for pair in drug_market.into_iter().zip_longest(drugs_owned_sorted) {
         match pair {
             itertools::EitherOrBoth::Both(market, owned) => {
                 let market_drug_name = match drugs_owned.contains_key(market.0) {
@@ -278,15 +390,15 @@ pub fn render_market(
                     false => owned.0.name.to_owned(),
                 };
 
+                let price = market.1.current_price(settings.day_duration);
+
                 let mut msg = Msg::new();
-                msg = if market.1.price >= market.0.nominal_price {
+                msg = if price >= market.0.nominal_price {
                     msg.color(Color::Green)
                         .text("↗ ")
-                        .text(market.1.price.pretty_money())
+                        .text(price.pretty_money())
                 } else {
-                    msg.color(Color::Red)
-                        .text("↘ ")
-                        .text(market.1.price.pretty_money())
+                    msg.color(Color::Red).text("↘ ").text(price.pretty_money())
                 };
 
                 msg = msg.reset();
@@ -314,15 +426,15 @@ pub fn render_market(
                     false => market.0.name.to_owned(),
                 };
 
+                let price = market.1.current_price(settings.day_duration);
+
                 let mut msg = Msg::new();
-                msg = if market.1.price >= market.0.nominal_price {
+                msg = if price >= market.0.nominal_price {
                     msg.color(Color::Green)
                         .text("↗ ")
-                        .text(market.1.price.pretty_money())
+                        .text(price.pretty_money())
                 } else {
-                    msg.color(Color::Red)
-                        .text("↘ ")
-                        .text(market.1.price.pretty_money())
+                    msg.color(Color::Red).text("↘ ").text(price.pretty_money())
                 };
 
                 msg = msg.reset();
@@ -363,7 +475,7 @@ pub fn render_market(
             "Demand".to_owned(),
             "Price".to_owned(),
         ])
-        .sizes([18, 10, 10, 19]);
+        .sizes([name_width, 10, 10, 19]);
 
     let mut items_owned_content = RenderBoxContent::new();
     items_owned_content
@@ -372,9 +484,12 @@ pub fn render_market(
             "Amount".to_owned(),
             "Bought at".to_owned(),
         ])
-        .sizes([18, 10, 25]);
+        .sizes([name_width, 10, 25]);
+
+    let item_market = sorted_market(&location.item_market, view.sort, settings.day_duration);
+    let items_owned_sorted = items_owned.iter().sorted_by(|a, b| a.0.name.cmp(&b.0.name));
 
-    for pair in location.item_market.iter().zip_longest(items_owned.iter()) {
+    for pair in item_market.into_iter().zip_longest(items_owned_sorted) {
         match pair {
             itertools::EitherOrBoth::Both(market, owned) => {
                 let market_item_name = match items_owned.contains_key(market.0) {
@@ -395,15 +510,15 @@ pub fn render_market(
                     false => owned.0.name.to_owned(),
                 };
 
+                let price = market.1.current_price(settings.day_duration);
+
                 let mut msg = Msg::new();
-                msg = if market.1.price >= market.0.nominal_price {
+                msg = if price >= market.0.nominal_price {
                     msg.color(Color::Green)
                         .text("↗ ")
-                        .text(market.1.price.pretty_money())
+                        .text(price.pretty_money())
                 } else {
-                    msg.color(Color::Red)
-                        .text("↘ ")
-                        .text(market.1.price.pretty_money())
+                    msg.color(Color::Red).text("↘ ").text(price.pretty_money())
                 };
 
                 msg = msg.reset();
@@ -431,15 +546,15 @@ pub fn render_market(
                     false => market.0.name.to_owned(),
                 };
 
+                let price = market.1.current_price(settings.day_duration);
+
                 let mut msg = Msg::new();
-                msg = if market.1.price >= market.0.nominal_price {
+                msg = if price >= market.0.nominal_price {
                     msg.color(Color::Green)
                         .text("↗ ")
-                        .text(market.1.price.pretty_money())
+                        .text(price.pretty_money())
                 } else {
-                    msg.color(Color::Red)
-                        .text("↘ ")
-                        .text(market.1.price.pretty_money())
+                    msg.color(Color::Red).text("↘ ").text(price.pretty_money())
                 };
 
                 msg = msg.reset();
@@ -489,8 +604,7 @@ pub fn render_market(
             "Drug market".to_owned(),
             format!(
                 "Owned drugs ({}/{})",
-                //pretty_print_amount(dealer.get_total_owned_local::<Drug>()),
-                0.pretty_amount(),
+                dealer.get_total_held_local::<Drug>().pretty_amount(),
                 dealer.capacity.pretty_amount(),
             ),
         ])
@@ -502,8 +616,7 @@ pub fn render_market(
             "Item market".to_owned(),
             format!(
                 "Owned items ({}/{})",
-                //pretty_print_amount(dealer.get_total_owned_local::<Item>()),
-                0.pretty_amount(),
+                dealer.get_total_owned_local::<Item>().pretty_amount(),
                 dealer.capacity.pretty_amount(),
             ),
         ])
@@ -518,7 +631,8 @@ pub fn render_market(
     renderer.build()
 }
 
-pub fn render_people(width: usize, location: &SingleLocationData) -> Vec<String> {
+pub fn render_people(view: &View, location: &SingleLocationData) -> Vec<String> {
+    let width = view.width;
     let mut blokes = location.people.iter().collect::<Vec<_>>();
     let mut line = String::new();
 
@@ -544,7 +658,7 @@ pub fn render_people(width: usize, location: &SingleLocationData) -> Vec<String>
     }
     let blokes_content = blokes_content.get();
 
-    Renderer::new(width)
+    renderer(view, width)
         .add_box(
             &RenderBox::new()
                 .headers(["People in town".to_owned()])
@@ -554,92 +668,425 @@ pub fn render_people(width: usize, location: &SingleLocationData) -> Vec<String>
         .build()
 }
 
-pub fn render_prices_from(current_location: &Location, locations: &Locations) -> Vec<String> {
+pub fn render_prices_from(
+    settings: &Settings,
+    view: &View,
+    current_location: &Arc<Location>,
+    locations: &Locations,
+    mode: &Arc<TravelMode>,
+    travel_modes: &TravelModes,
+    routes: &Routes,
+) -> Vec<String> {
     let mut flight_prices_content = RenderBoxContent::new();
 
     flight_prices_content
-        .header(["To".to_owned(), "Price".to_owned()])
-        .sizes([30, 15]);
+        .header([
+            "To".to_owned(),
+            "Price".to_owned(),
+            "Arrival".to_owned(),
+            "Stops".to_owned(),
+            "Modes".to_owned(),
+        ])
+        .sizes([26, 15, 8, 7, 22]);
 
     for location in locations.iter() {
         if location.name == current_location.name {
             continue;
         }
 
-        let price = get_flight_price(current_location, location);
-
         let to = Msg::new()
             .color(Color::Yellow)
             .text(&location.name)
             .reset()
             .to_string();
+
+        let modes = travel_modes
+            .iter()
+            .filter(|mode| routes.find(current_location, location, mode).is_some())
+            .map(|mode| mode.name.as_str())
+            .join(", ");
+
+        let Some(route) = routes.find(current_location, location, mode) else {
+            flight_prices_content.add_row([
+                to,
+                "-".to_owned(),
+                "-".to_owned(),
+                "-".to_owned(),
+                modes,
+            ]);
+            continue;
+        };
+
+        let price = get_route_price(current_location, &route, mode);
         let p_price = Msg::new()
             .color(Color::Green)
             .text(price.pretty_money())
             .reset()
             .to_string();
 
-        flight_prices_content.add_row([to, p_price]);
+        let days = get_route_days(current_location, &route, mode)
+            .iter()
+            .sum::<i64>();
+        let arrival = (settings.current_day + Duration::days(days))
+            .format("%m-%d")
+            .to_string();
+
+        flight_prices_content.add_row([to, p_price, arrival, (route.len() - 1).to_string(), modes]);
     }
 
-    Renderer::new(50)
+    renderer(view, 75)
         .add_box(
             &RenderBox::new()
-                .headers([format!("Flight prices from {}", &current_location.name)])
+                .headers([format!(
+                    "Prices by {} from {}",
+                    mode.name, &current_location.name
+                )])
                 .add_content([&flight_prices_content.get()])
                 .get(),
         )
         .build()
 }
 
-/*
-impl DrugWars {
+pub fn render_travel_modes(
+    settings: &Settings,
+    view: &View,
+    current_location: &Arc<Location>,
+    destination: &Arc<Location>,
+    travel_modes: &TravelModes,
+    routes: &Routes,
+) -> Vec<String> {
+    let mut modes_content = RenderBoxContent::new();
+
+    modes_content
+        .header([
+            "Mode".to_owned(),
+            "Price".to_owned(),
+            "Arrival".to_owned(),
+            "Risk".to_owned(),
+            "Via".to_owned(),
+        ])
+        .sizes([10, 15, 8, 6, 30]);
+
+    for mode in travel_modes.iter() {
+        let Some(route) = routes.find(current_location, destination, mode) else {
+            continue;
+        };
+
+        let price = get_route_price(current_location, &route, mode);
+        let days = get_route_days(current_location, &route, mode)
+            .iter()
+            .sum::<i64>();
+        let via = route[..route.len() - 1]
+            .iter()
+            .map(|location| location.name.as_str())
+            .join(", ");
+
+        let name = Msg::new()
+            .color(Color::Yellow)
+            .text(&mode.name)
+            .reset()
+            .to_string();
+        let p_price = Msg::new()
+            .color(Color::Green)
+            .text(price.pretty_money())
+            .reset()
+            .to_string();
+        let arrival = (settings.current_day + Duration::days(days))
+            .format("%m-%d")
+            .to_string();
+        let risk = format!("{:.0}%", mode.risk * 100.);
+
+        modes_content.add_row([name, p_price, arrival, risk, via]);
+    }
+
+    renderer(view, 66)
+        .add_box(
+            &RenderBox::new()
+                .headers([format!(
+                    "{} to {}",
+                    &current_location.name, &destination.name
+                )])
+                .add_content([&modes_content.get()])
+                .get(),
+        )
+        .build()
+}
+
+pub fn render_news(view: &View, day: &str, news: &[(&Location, &Vec<String>)]) -> Vec<String> {
+    let width = view.width;
+    let mut news_content = RenderBoxContent::new();
+    news_content.sizes([25, width - 28]);
+
+    for (location, headlines) in news {
+        for (idx, headline) in headlines.iter().enumerate() {
+            let location_name = match idx {
+                0 => Msg::new()
+                    .color(Color::Purple)
+                    .text(&location.name)
+                    .reset()
+                    .to_string(),
+                _ => "".to_owned(),
+            };
+
+            news_content.add_row([location_name, headline.clone()]);
+        }
+    }
+
+    if news.is_empty() {
+        news_content.add_row(["nothing happened".to_owned(), "".to_owned()]);
+    }
+
+    renderer(view, width)
+        .add_box(
+            &RenderBox::new()
+                .headers([format!("News from {}", day)])
+                .add_content([&news_content.get()])
+                .get(),
+        )
+        .build()
+}
+
+pub fn render_price_history(
+    view: &View,
+    location: &Location,
+    drug: &Drug,
+    history: Option<&VecDeque<Option<Money>>>,
+) -> Vec<String> {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let prices = history
+        .map(|history| history.iter().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let known = prices.iter().filter_map(|price| price.as_ref());
+    let min = known.clone().min();
+    let max = known.max();
+
+    let mut history_content = RenderBoxContent::new();
+    history_content.sizes([10, 35]);
+
+    match (min, max) {
+        (Some(min), Some(max)) => {
+            let spread = max - min;
+
+            let sparkline = prices
+                .iter()
+                .map(|price| match price {
+                    Some(price) if spread.is_positive() => {
+                        let level = (price - min).ratio(&spread) * (BARS.len() - 1) as f64;
+                        BARS[(level as usize).min(BARS.len() - 1)]
+                    }
+                    Some(_) => BARS[0],
+                    None => ' ',
+                })
+                .collect::<String>();
+
+            let today = match prices.last() {
+                Some(Some(price)) => price.pretty_money(),
+                _ => "not sold".to_owned(),
+            };
+
+            history_content
+                .add_row([
+                    format!("{} days", prices.len()),
+                    Msg::new()
+                        .color(Color::Yellow)
+                        .text(sparkline)
+                        .reset()
+                        .to_string(),
+                ])
+                .add_row(["lowest".to_owned(), min.pretty_money()])
+                .add_row(["highest".to_owned(), max.pretty_money()])
+                .add_row(["today".to_owned(), today]);
+        }
+        _ => {
+            history_content.add_row(["never sold".to_owned(), "".to_owned()]);
+        }
+    }
 
-    pub fn render_leaderboard(&self) -> Vec<String> {
-        let dealers = &self
-            .dealers
+    renderer(view, 50)
+        .add_box(
+            &RenderBox::new()
+                .headers([format!("{} in {}", drug.name, location.name)])
+                .add_content([&history_content.get()])
+                .get(),
+        )
+        .build()
+}
+
+pub fn render_leaderboard(view: &View, dealers: &[(String, Money)]) -> Vec<String> {
+    let mut leaderboard_content = RenderBoxContent::new();
+    leaderboard_content
+        .header([
+            "Dealer".to_owned(),
+            "Place".to_owned(),
+            "Laundered money".to_owned(),
+        ])
+        .sizes([12, 8, 25]);
+
+    for (idx, (nick, laundered_money)) in dealers.iter().enumerate() {
+        let msg = Msg::new()
+            .color(Color::Green)
+            .text(laundered_money.pretty_money())
+            .reset();
+
+        leaderboard_content.add_row([nick.clone(), (idx + 1).to_string(), msg.to_string()]);
+    }
+
+    renderer(view, 50)
+        .add_box(
+            &RenderBox::new()
+                .headers([format!("Top {} hardest dealers", dealers.len())])
+                .add_content([&leaderboard_content.get()])
+                .get(),
+        )
+        .build()
+}
+
+/// Nothing at all on quiet days, the standings alone aren't news.
+pub fn render_daily_summary(
+    width: usize,
+    day: &str,
+    movers: &[PriceMove],
+    rumors: &[Rumor],
+    deaths: &[String],
+    leaderboard: &[(String, Money)],
+) -> Vec<String> {
+    if movers.is_empty() && rumors.is_empty() && deaths.is_empty() {
+        return vec![];
+    }
+
+    let mut summary_content = RenderBoxContent::new();
+    summary_content.sizes([15, width - 18]);
+
+    let mut add_section = |title: &str, rows: Vec<String>| {
+        for (idx, row) in rows.into_iter().enumerate() {
+            let title = match idx {
+                0 => title.to_owned(),
+                _ => "".to_owned(),
+            };
+            summary_content.add_row([title, row]);
+        }
+    };
+
+    add_section(
+        "Price movers",
+        movers
+            .iter()
+            .map(|price_move| {
+                let color = match price_move.to >= price_move.from {
+                    true => Color::Green,
+                    false => Color::Red,
+                };
+
+                Msg::new()
+                    .color(Color::Yellow)
+                    .text(&price_move.drug.name)
+                    .reset()
+                    .text(" in ")
+                    .color(Color::Purple)
+                    .text(&price_move.location.name)
+                    .reset()
+                    .text(format!(
+                        ": {} -> {} ",
+                        price_move.from.pretty_money(),
+                        price_move.to.pretty_money()
+                    ))
+                    .color(color)
+                    .text(format!("({:+.2}%)", price_move.change()))
+                    .reset()
+                    .to_string()
+            })
+            .collect(),
+    );
+
+    add_section(
+        "Rumors",
+        rumors
+            .iter()
+            .map(|rumor| {
+                let trend = match rumor.trend {
+                    PriceTrend::Up => "scarce",
+                    PriceTrend::Down => "abundant",
+                };
+
+                let (color, outcome) = match rumor.confirmed {
+                    Some(true) => (Color::Green, "confirmed"),
+                    _ => (Color::Red, "debunked"),
+                };
+
+                Msg::new()
+                    .color(Color::Yellow)
+                    .text(&rumor.drug.name)
+                    .reset()
+                    .text(format!(" {} in ", trend))
+                    .color(Color::Purple)
+                    .text(&rumor.location.name)
+                    .reset()
+                    .text(": ")
+                    .color(color)
+                    .text(outcome)
+                    .reset()
+                    .to_string()
+            })
+            .collect(),
+    );
+
+    if !deaths.is_empty() {
+        add_section("Deaths", vec![deaths.join(", ")]);
+    }
+
+    add_section(
+        "Top dealers",
+        leaderboard
             .iter()
-            .sorted_by_key(|(_, k)| k.laundered_money)
-            .rev()
             .enumerate()
-            .collect::<Vec<_>>();
-
-        let min = dealers.len().min(5);
-
-        let dealers = &dealers[0..min];
-
-        let mut leaderboard_content = RenderBoxContent::new();
-        leaderboard_content
-            .header([
-                "Dealer".to_owned(),
-                "Place".to_owned(),
-                "Laundered money".to_owned(),
-            ])
-            .sizes([12, 8, 25]);
-
-        for (idx, (name, dealer)) in dealers {
-            let mut msg = PrivMsg::new();
-            let msg = msg
-                .color(IrcColor::Green)
-                .text(&pretty_print_money(dealer.laundered_money))
-                .reset()
-                .get();
-
-            leaderboard_content.add_row([
-                name.to_owned().clone(),
-                (idx + 1).to_string(),
-                msg.to_owned(),
-            ]);
-        }
+            .map(|(idx, (nick, laundered_money))| {
+                Msg::new()
+                    .text(format!("{}. {} ", idx + 1, nick))
+                    .color(Color::Green)
+                    .text(laundered_money.pretty_money())
+                    .reset()
+                    .to_string()
+            })
+            .collect(),
+    );
+
+    Renderer::new(width)
+        .add_box(
+            &RenderBox::new()
+                .headers([format!("What happened on {}", day)])
+                .add_content([&summary_content.get()])
+                .get(),
+        )
+        .build()
+}
 
-        Renderer::new(50)
-            .add_box(
-                &RenderBox::new()
-                    .headers(["Top 5 hardest dealers".to_owned()])
-                    .add_content([&leaderboard_content])
-                    .get(),
-            )
-            .build()
+pub fn render_events(view: &View, nick: &str, entries: &[LogEntry]) -> Vec<String> {
+    let width = view.width;
+    let mut events_content = RenderBoxContent::new();
+    events_content
+        .header([
+            "Day".to_owned(),
+            "Time (UTC)".to_owned(),
+            "Event".to_owned(),
+        ])
+        .sizes([12, 22, width - 37]);
+
+    for entry in entries {
+        events_content.add_row([
+            entry.day.format("%Y-%m-%d").to_string(),
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+            entry.event.describe(),
+        ]);
     }
+
+    renderer(view, width)
+        .add_box(
+            &RenderBox::new()
+                .headers([format!("Last {} events of {}", entries.len(), nick)])
+                .add_content([&events_content.get()])
+                .get(),
+        )
+        .build()
 }
- */
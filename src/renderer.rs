@@ -2,11 +2,24 @@ use crate::utils::StringManips;
 
 pub trait BoxContent {
     fn get_lines(&self, width: usize) -> Vec<String>;
+    /// The rows as plain text, cells separated by spaces.
+    fn get_compact_lines(&self) -> Vec<String>;
     fn len(&self) -> usize;
 }
 
 pub trait Part {
     fn get_lines(&self, width: usize) -> Vec<String>;
+    /// The part without its borders, for small screens.
+    fn get_compact_lines(&self) -> Vec<String>;
+}
+
+fn compact_row(cells: &[String]) -> String {
+    cells
+        .iter()
+        .map(|cell| cell.trim())
+        .filter(|cell| !cell.is_empty())
+        .collect::<Vec<_>>()
+        .join("  ")
 }
 
 #[derive(Default, Clone)]
@@ -83,6 +96,15 @@ impl<const N: usize> BoxContent for RenderBoxContent<N> {
         lines
     }
 
+    fn get_compact_lines(&self) -> Vec<String> {
+        self.header
+            .iter()
+            .chain(self.content.iter())
+            .map(|row| compact_row(row))
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+
     fn len(&self) -> usize {
         let mut len = self.content.len();
 
@@ -223,9 +245,26 @@ impl<'a, const N: usize> Part for RenderBox<'a, N> {
 
         lines
     }
+
+    fn get_compact_lines(&self) -> Vec<String> {
+        let mut lines = vec![];
+
+        for col_index in 0..N {
+            if let Some(headers) = &self.headers {
+                lines.push(format!("── {} ──", headers[col_index]));
+            }
+
+            if let Some(columns) = self.columns {
+                lines.extend(columns[col_index].get_compact_lines());
+            }
+        }
+
+        lines
+    }
 }
 pub struct Renderer<'a> {
     width: usize,
+    compact: bool,
     boxes: Vec<&'a dyn Part>,
 }
 
@@ -233,10 +272,16 @@ impl<'a> Renderer<'a> {
     pub fn new(width: usize) -> Self {
         Self {
             width: width,
+            compact: false,
             boxes: vec![],
         }
     }
 
+    pub fn compact(&mut self, compact: bool) -> &mut Self {
+        self.compact = compact;
+        self
+    }
+
     pub fn add_box(&mut self, element: &'a impl Part) -> &mut Self {
         self.boxes.push(element);
         self
@@ -246,8 +291,10 @@ impl<'a> Renderer<'a> {
         let out = self
             .boxes
             .iter()
-            .map(|elem| elem.get_lines(self.width))
-            .flatten()
+            .flat_map(|elem| match self.compact {
+                true => elem.get_compact_lines(),
+                false => elem.get_lines(self.width),
+            })
             .collect::<Vec<_>>();
 
         out
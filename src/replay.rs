@@ -0,0 +1,477 @@
+use std::collections::HashMap;
+
+use chrono::Duration;
+use serde_yaml::Value;
+
+use crate::{
+    config::Settings,
+    customs::{Cargo, CustomsHold},
+    dealer::{merge_owned, Dealer, DealerStatus},
+    error::{Error, Result},
+    event_log::{log_cargo, ElementKind, EventLog, GameEvent, LogEntry, LoggedCargo},
+    resources::{DrugWarsRng, GameData, Matching},
+    save::{load_save, SaveData},
+    state::GameState,
+    utils::Amount,
+};
+
+/// Which events, by index, the game after the first `position` ones is made
+/// of. A restart goes back to its save and a rollback to its snapshot,
+/// dropping whatever was logged after them.
+pub fn history(entries: &[LogEntry], position: usize) -> Vec<usize> {
+    // the last event each prefix of the log ends on, and the event before
+    // each one
+    let mut last = vec![None; entries.len() + 1];
+    let mut previous = vec![None; entries.len()];
+
+    for (idx, entry) in entries.iter().enumerate() {
+        previous[idx] = match entry.event {
+            GameEvent::GameStarted {
+                resumed_from: Some(resumed),
+                ..
+            }
+            | GameEvent::RolledBack {
+                resumed_from: Some(resumed),
+            } => last[resumed.min(idx)],
+            GameEvent::GameStarted {
+                resumed_from: None, ..
+            }
+            | GameEvent::RolledBack { resumed_from: None } => None,
+            _ => last[idx],
+        };
+        last[idx + 1] = Some(idx);
+    }
+
+    let mut events = vec![];
+    let mut current = last[position.min(entries.len())];
+
+    while let Some(idx) = current {
+        events.push(idx);
+        current = previous[idx];
+    }
+
+    events.reverse();
+    events
+}
+
+/// Rebuilds a game from its event log. Markets are rolled again from the
+/// seed, everything dealers did is applied as it was logged.
+pub struct Replay<'a> {
+    game_data: &'a GameData,
+    pub settings: Settings,
+    pub state: GameState,
+    /// Cargo that landed and waits for its customs outcome.
+    customs: HashMap<String, Cargo>,
+}
+
+fn diverged(reason: impl Into<String>) -> Error {
+    Error::Diverged(reason.into())
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(game_data: &'a GameData, settings: Settings) -> Self {
+        Self {
+            game_data,
+            settings,
+            state: GameState::new(&game_data.locations),
+            customs: HashMap::default(),
+        }
+    }
+
+    pub fn from_save(game_data: &'a GameData, settings: Settings, save: SaveData) -> Result<Self> {
+        let mut replay = Self::new(game_data, settings);
+        save.restore(game_data, &mut replay.settings, &mut replay.state)?;
+
+        Ok(replay)
+    }
+
+    /// Replays the events of `entries` the game up to `position` is made of,
+    /// starting after the ones `from` covers.
+    pub fn run(&mut self, entries: &[LogEntry], from: usize, position: usize) -> Result<()> {
+        let events = history(entries, position);
+        let skipped = history(entries, from);
+
+        if !events.starts_with(&skipped) {
+            return Err(diverged("the snapshot isn't part of the saved game"));
+        }
+
+        for idx in events.into_iter().skip(skipped.len()) {
+            self.apply(&entries[idx])
+                .map_err(|err| Error::Replay(idx + 1, err.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    fn dealer(&mut self, nick: Option<&str>) -> Result<&mut Dealer> {
+        let nick = nick.ok_or_else(|| diverged("no dealer given"))?;
+        self.state.dealers.get_dealer_mut(nick)
+    }
+
+    fn take_customs(&mut self, nick: &str, logged: &LoggedCargo) -> Result<Cargo> {
+        let cargo = self.customs.remove(nick).unwrap_or_default();
+
+        if &log_cargo(&cargo) != logged {
+            return Err(diverged(format!(
+                "{} went through customs with other cargo",
+                nick
+            )));
+        }
+
+        Ok(cargo)
+    }
+
+    pub fn apply(&mut self, entry: &LogEntry) -> Result<()> {
+        let game_data = self.game_data;
+        let nick = entry.nick.as_deref();
+
+        match &entry.event {
+            GameEvent::GameStarted {
+                seed, new_markets, ..
+            } => {
+                self.settings.seed = *seed;
+                self.settings.current_day = entry.day;
+
+                // a resumed game picks up the markets it was saved with
+                if *new_markets {
+                    let mut rng = DrugWarsRng::for_startup(*seed, entry.day);
+                    self.state.locations.update(game_data, &mut rng.0);
+                }
+            }
+            // the snapshot was taken once the day's markets were out
+            GameEvent::RolledBack { .. } => {
+                self.settings.current_day = entry.day;
+            }
+            GameEvent::DayStarted => {
+                self.settings.current_day += Duration::days(1);
+
+                if self.settings.current_day != entry.day {
+                    return Err(diverged(format!(
+                        "the game was on {} instead",
+                        self.settings.current_day
+                    )));
+                }
+
+                let mut rng = DrugWarsRng::for_day(self.settings.seed, entry.day);
+                self.state.locations.update(game_data, &mut rng.0);
+            }
+            GameEvent::DaysSkipped { .. } | GameEvent::GameSaved => {}
+            GameEvent::Registered { location, money } => {
+                let nick = nick.ok_or_else(|| diverged("no dealer given"))?;
+                let location = game_data.locations.get_by_name(location)?.clone();
+
+                self.state.dealers.insert(
+                    nick.to_owned(),
+                    Dealer::new(nick, location, money.clone(), &game_data.locations),
+                );
+            }
+            GameEvent::Bought {
+                kind,
+                element,
+                amount,
+                total,
+                ..
+            } => {
+                let dealer = self.dealer(nick)?;
+
+                match kind {
+                    ElementKind::Drug => {
+                        let drug = game_data.drugs.get_by_name(element)?;
+                        dealer.settle_buy(drug, *amount, total)
+                    }
+                    ElementKind::Item => {
+                        let item = game_data.items.get_by_name(element)?;
+                        dealer.settle_buy(item, *amount, total)
+                    }
+                }
+            }
+            GameEvent::Sold {
+                kind,
+                element,
+                amount,
+                total,
+                ..
+            } => {
+                let dealer = self.dealer(nick)?;
+
+                match kind {
+                    ElementKind::Drug => {
+                        let drug = game_data.drugs.get_by_name(element)?;
+                        dealer.settle_sell(drug, *amount, total)?
+                    }
+                    ElementKind::Item => {
+                        let item = game_data.items.get_by_name(element)?;
+                        dealer.settle_sell(item, *amount, total)?
+                    }
+                }
+            }
+            GameEvent::Departed {
+                destination,
+                mode,
+                price,
+                arrival,
+                ..
+            } => {
+                let destination = game_data.locations.get_by_name(destination)?;
+                let mode = game_data.travel_modes.get_by_name(mode)?;
+
+                let state = &mut self.state;
+                let dealer = state.dealers.get_dealer_mut(nick.unwrap_or_default())?;
+                let data = state.locations.get_mut(&dealer.location).unwrap();
+                let money = dealer.money.clone();
+
+                dealer.fly_to(
+                    &mut state.flights,
+                    &game_data.routes,
+                    destination,
+                    mode,
+                    data,
+                    entry.day,
+                )?;
+
+                if &(money - &dealer.money) != price
+                    || state.flights[&dealer.nick].arrival != *arrival
+                {
+                    return Err(diverged("the trip went another way"));
+                }
+            }
+            GameEvent::Layover { location } => {
+                let state = &mut self.state;
+                let dealer = state.dealers.get_dealer_mut(nick.unwrap_or_default())?;
+                let flight = state
+                    .flights
+                    .get_mut(&dealer.nick)
+                    .ok_or_else(|| diverged("no flight to stop over from"))?;
+
+                let layover = match flight.layovers.pop_front() {
+                    Some((layover, _)) if &layover.name == location => layover,
+                    _ => return Err(diverged(format!("no stop planned in {}", location))),
+                };
+
+                dealer.location = layover.clone();
+                dealer.status = DealerStatus::Available;
+
+                let data = state.locations.get_mut(&layover).unwrap();
+                data.people.insert(dealer.nick.clone());
+                flight.layover = Some(layover);
+            }
+            GameEvent::LeftLayover { .. } => {
+                let state = &mut self.state;
+                let dealer = state.dealers.get_dealer_mut(nick.unwrap_or_default())?;
+                let flight = state
+                    .flights
+                    .get_mut(&dealer.nick)
+                    .ok_or_else(|| diverged("not on a layover"))?;
+                let layover = flight
+                    .layover
+                    .take()
+                    .ok_or_else(|| diverged("not on a layover"))?;
+
+                dealer.status = DealerStatus::Flying;
+                merge_owned(&mut flight.cargo, std::mem::take(&mut dealer.carry_on));
+
+                let data = state.locations.get_mut(&layover).unwrap();
+                data.people.remove(&dealer.nick);
+            }
+            GameEvent::Landed { .. } => {
+                let state = &mut self.state;
+                let dealer = state.dealers.get_dealer_mut(nick.unwrap_or_default())?;
+                let flight = state
+                    .flights
+                    .remove(&dealer.nick)
+                    .ok_or_else(|| diverged("no flight to land"))?;
+
+                dealer.location = flight.destination.clone();
+                dealer.status = DealerStatus::Available;
+
+                let data = state.locations.get_mut(&flight.destination).unwrap();
+                data.people.insert(dealer.nick.clone());
+                self.customs.insert(dealer.nick.clone(), flight.cargo);
+            }
+            GameEvent::Stayed { .. } => {
+                let dealer = self.dealer(nick)?.nick.clone();
+                let flight = self
+                    .state
+                    .flights
+                    .remove(&dealer)
+                    .filter(|flight| flight.layover.is_some())
+                    .ok_or_else(|| diverged("not on a layover"))?;
+
+                self.customs.insert(dealer, flight.cargo);
+            }
+            GameEvent::Robbed { lost, .. } => {
+                self.dealer(nick)?.money -= lost;
+            }
+            GameEvent::Packed { drug, amount } => {
+                let drug = game_data.drugs.get_by_name(drug)?;
+                self.dealer(nick)?.pack(drug, &Amount::Units(*amount))?;
+            }
+            GameEvent::Unpacked { drug, amount } => {
+                let drug = game_data.drugs.get_by_name(drug)?;
+                self.dealer(nick)?.unpack(drug, &Amount::Units(*amount))?;
+            }
+            GameEvent::CustomsCleared { cargo, .. } => {
+                let cargo = self.take_customs(nick.unwrap_or_default(), cargo)?;
+                self.dealer(nick)?.stash(cargo);
+            }
+            GameEvent::CustomsSeized { cargo, .. } | GameEvent::FineUnpaid { cargo, .. } => {
+                self.take_customs(nick.unwrap_or_default(), cargo)?;
+            }
+            GameEvent::CustomsFined { cargo, fine, .. } => {
+                let cargo = self.take_customs(nick.unwrap_or_default(), cargo)?;
+                let dealer = self.dealer(nick)?;
+
+                dealer.money -= fine;
+                dealer.stash(cargo);
+            }
+            GameEvent::CustomsHeld { cargo, bribe, .. } => {
+                let cargo = self.take_customs(nick.unwrap_or_default(), cargo)?;
+                let dealer = self.dealer(nick)?;
+
+                dealer.customs_hold = Some(CustomsHold {
+                    location: dealer.location.clone(),
+                    cargo,
+                    bribe: bribe.clone(),
+                });
+            }
+            GameEvent::Bribed { bribe, .. } => {
+                let dealer = self.dealer(nick)?;
+
+                if dealer.customs_hold.as_ref().map(|hold| &hold.bribe) != Some(bribe) {
+                    return Err(diverged("customs asked for another bribe"));
+                }
+
+                dealer.bribe()?;
+            }
+            GameEvent::HoldKept { .. } => {
+                self.dealer(nick)?
+                    .customs_hold
+                    .take()
+                    .ok_or_else(|| diverged("customs wasn't holding anything"))?;
+            }
+            GameEvent::PreferenceSet { name, value } => {
+                self.dealer(nick)?.preferences.set(name, value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Replays the event log up to where the save stands, from the start or
+/// from an older `snapshot`, and tells where the result differs from the
+/// save.
+pub async fn verify_save(
+    game_data: &GameData,
+    settings: Settings,
+    snapshot: Option<&str>,
+) -> Result<Vec<String>> {
+    let saved = load_save(&settings.save_path)
+        .await?
+        .ok_or_else(|| diverged("there's no save to check"))?;
+    let position = saved
+        .log_position
+        .ok_or_else(|| diverged("the save is older than the event log"))?;
+    let entries = EventLog::read_all(&settings.event_log_path)?;
+
+    let (mut replay, from) = match snapshot {
+        Some(path) => {
+            let snapshot = load_save(path)
+                .await?
+                .ok_or_else(|| diverged(format!("there's no snapshot at {}", path)))?;
+            let from = snapshot
+                .log_position
+                .ok_or_else(|| diverged("the snapshot is older than the event log"))?;
+
+            (Replay::from_save(game_data, settings, snapshot)?, from)
+        }
+        None => (Replay::new(game_data, settings), 0),
+    };
+
+    replay.run(&entries, from, position)?;
+
+    let replayed = SaveData::new(&replay.settings, &replay.state, position);
+    compare(&saved, &replayed)
+}
+
+/// Where two saves differ, as `path: saved -> replayed`. When the day
+/// started isn't compared, a replay can't know it.
+pub fn compare(saved: &SaveData, replayed: &SaveData) -> Result<Vec<String>> {
+    let mut differences = vec![];
+
+    diff(
+        "",
+        &comparable(saved)?,
+        &comparable(replayed)?,
+        &mut differences,
+    );
+
+    Ok(differences)
+}
+
+fn comparable(save: &SaveData) -> Result<Value> {
+    let mut value = serde_yaml::to_value(save)?;
+    let mapping = value.as_mapping_mut().unwrap();
+
+    mapping.remove("day_started");
+    // trades move markets within the day, the log only has what they cost
+    mapping.remove("markets");
+
+    // dealers are saved in no particular order
+    if let Some(Value::Sequence(dealers)) = mapping.remove("dealers") {
+        let dealers = dealers
+            .into_iter()
+            .map(|dealer| (dealer["nick"].clone(), dealer))
+            .collect();
+
+        mapping.insert("dealers".into(), Value::Mapping(dealers));
+    }
+
+    Ok(value)
+}
+
+fn describe(value: &Value) -> String {
+    serde_yaml::to_string(value)
+        .unwrap_or_default()
+        .trim()
+        .replace('\n', " ")
+}
+
+fn diff(path: &str, saved: &Value, replayed: &Value, differences: &mut Vec<String>) {
+    let child = |key: String| match path.is_empty() {
+        true => key,
+        false => format!("{}.{}", path, key),
+    };
+
+    match (saved, replayed) {
+        (Value::Mapping(saved), Value::Mapping(replayed)) => {
+            for (key, value) in saved.iter() {
+                let path = child(describe(key));
+
+                match replayed.get(key) {
+                    Some(other) => diff(&path, value, other, differences),
+                    None => differences.push(format!("{}: {} -> missing", path, describe(value))),
+                }
+            }
+
+            for (key, value) in replayed.iter() {
+                if !saved.contains_key(key) {
+                    let path = child(describe(key));
+                    differences.push(format!("{}: missing -> {}", path, describe(value)));
+                }
+            }
+        }
+        (Value::Sequence(saved), Value::Sequence(replayed)) if saved.len() == replayed.len() => {
+            for (idx, (value, other)) in saved.iter().zip(replayed.iter()).enumerate() {
+                diff(&child(idx.to_string()), value, other, differences);
+            }
+        }
+        _ if saved != replayed => differences.push(format!(
+            "{}: {} -> {}",
+            path,
+            describe(saved),
+            describe(replayed)
+        )),
+        _ => {}
+    }
+}
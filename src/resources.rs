@@ -1,18 +1,22 @@
 use std::{
-    any::TypeId,
-    collections::HashMap,
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet, VecDeque},
     hash::Hash,
     ops::{Deref, DerefMut},
     sync::Arc,
 };
 
+use chrono::{Datelike, NaiveDate};
 use itertools::Itertools;
-use num_bigint::BigInt;
-use rand::rngs::StdRng;
+use rand::{rngs::StdRng, SeedableRng};
 
 use crate::{
+    customs::Cargo,
     element::{ArcElement, Element},
     error::{Error, Result},
+    location_data::{Decay, PriceTrend},
+    money::Money,
+    utils::{edit_distance, get_distance, normalize_name},
 };
 
 #[derive(Debug)]
@@ -21,10 +25,74 @@ pub struct Position {
     pub long: f32,
 }
 
+#[derive(Debug, Clone)]
+pub struct DrugOverride {
+    /// The drug is never sold there.
+    pub banned: bool,
+    /// Applied on top of the location price multiplier, below 1 for producing regions.
+    pub price_multiplier: f64,
+    /// Replaces the drug availability from its profile.
+    pub availability: Option<f64>,
+}
+
+impl Default for DrugOverride {
+    fn default() -> Self {
+        Self {
+            banned: false,
+            price_multiplier: 1.,
+            availability: None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Location {
     pub name: String,
     pub position: Position,
+    pub region: String,
+    pub coastal: bool,
+    /// Chance to get a carry-on of `CUSTOMS_UNITS` inspected when landing.
+    pub customs: f64,
+    pub price_multiplier: f64,
+    pub drug_overrides: HashMap<Arc<Drug>, DrugOverride>,
+    pub aliases: Vec<String>,
+}
+
+impl Location {
+    pub fn drug_price(&self, drug: &Arc<Drug>) -> Money {
+        let local_multiplier = self
+            .drug_overrides
+            .get(drug)
+            .map_or(1., |drug_override| drug_override.price_multiplier);
+
+        drug.nominal_price
+            .scale(self.price_multiplier * local_multiplier)
+    }
+
+    pub fn item_price(&self, item: &Item) -> Money {
+        item.nominal_price.scale(self.price_multiplier)
+    }
+
+    pub fn sells_drug(&self, drug: &Arc<Drug>) -> bool {
+        !self
+            .drug_overrides
+            .get(drug)
+            .is_some_and(|drug_override| drug_override.banned)
+    }
+
+    pub fn drug_availability(&self, drug: &Arc<Drug>) -> f64 {
+        self.drug_overrides
+            .get(drug)
+            .and_then(|drug_override| drug_override.availability)
+            .unwrap_or(drug.profile.availability)
+    }
+
+    pub fn sells_item(&self, item: &Item) -> bool {
+        match &item.only_in {
+            Some(locations) => locations.iter().any(|location| location.name == self.name),
+            None => true,
+        }
+    }
 }
 
 impl PartialEq for Location {
@@ -43,10 +111,38 @@ impl Hash for Location {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct DrugProfile {
+    /// Chance for the drug to show up on a market on a given day.
+    pub availability: f64,
+    /// Chance for a spontaneous price spike on a given day.
+    pub volatility: f64,
+    /// Price multiplier when the drug gets scarce.
+    pub spike_up: f64,
+    /// Price divider when the drug floods the market.
+    pub spike_down: f64,
+    /// Chance for a rumor about the drug to start on a given day.
+    pub rumor_frequency: f64,
+}
+
+impl Default for DrugProfile {
+    fn default() -> Self {
+        Self {
+            availability: 1. / 5.,
+            volatility: 0.08,
+            spike_up: 15.,
+            spike_down: 6.,
+            rumor_frequency: 0.05,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Drug {
     pub name: String,
-    pub nominal_price: BigInt,
+    pub nominal_price: Money,
+    pub profile: DrugProfile,
+    pub aliases: Vec<String>,
 }
 
 impl PartialEq for Drug {
@@ -68,8 +164,11 @@ impl Hash for Drug {
 #[derive(Debug)]
 pub struct Item {
     pub name: String,
-    pub nominal_price: BigInt,
+    pub nominal_price: Money,
     pub kind: ItemKind,
+    /// Restricts the item to these locations when set.
+    pub only_in: Option<Vec<Arc<Location>>>,
+    pub aliases: Vec<String>,
 }
 
 impl PartialEq for Item {
@@ -106,32 +205,119 @@ pub struct Weapon {
     pub damage: f32,
 }
 
+/// Shorter searches are too vague to guess typos from.
+const MIN_FUZZY_LENGTH: usize = 3;
+
+fn ambiguous<E: ArcElement>(val: &str, matching: &[&E]) -> Error {
+    Error::ElementAmbiguous(
+        val.to_owned(),
+        matching
+            .iter()
+            .map(|elem| elem.name().to_owned())
+            .sorted()
+            .collect(),
+    )
+}
+
 pub trait Matching {
     type Elem: ArcElement;
 
+    /// Looks for an exact name or alias, then a prefix, then a substring
+    /// and finally tolerates a few typos.
     fn get_matching(&self, val: &str) -> Result<&Self::Elem>
     where
         Self: Deref<Target = Vec<Self::Elem>>,
     {
-        let val = val.to_lowercase();
-        let matching = self
+        let val = normalize_name(val);
+
+        if val.is_empty() {
+            return Err(Error::ElementNotFound(val));
+        }
+
+        let names = |elem: &Self::Elem| {
+            std::iter::once(elem.name())
+                .chain(elem.aliases().iter().map(|alias| alias.as_str()))
+                .map(normalize_name)
+                .collect::<Vec<_>>()
+        };
+
+        let stages: [&dyn Fn(&str) -> bool; 3] = [
+            &|name| name == val,
+            &|name| name.starts_with(&val),
+            &|name| name.contains(&val),
+        ];
+
+        for stage in stages {
+            let matching = self
+                .iter()
+                .filter(|elem| names(elem).iter().any(|name| stage(name)))
+                .collect::<Vec<_>>();
+
+            match matching.len() {
+                0 => continue,
+                1 => return Ok(matching[0]),
+                _ => return Err(ambiguous(&val, &matching)),
+            }
+        }
+
+        let length = val.chars().count();
+
+        if length < MIN_FUZZY_LENGTH {
+            return Err(Error::ElementNotFound(val));
+        }
+
+        let scored = self
             .iter()
-            .filter(|elem| {
-                elem.name()
-                    .to_lowercase()
-                    .replace(" ", "")
-                    .starts_with(&val)
+            .filter_map(|elem| {
+                names(elem)
+                    .iter()
+                    .map(|name| {
+                        let start = name.chars().take(length).collect::<String>();
+                        edit_distance(&val, name).min(edit_distance(&val, &start))
+                    })
+                    .min()
+                    .filter(|distance| *distance <= length / 3)
+                    .map(|distance| (distance, elem))
             })
-            .sorted_by_key(|elem| elem.name().len())
             .collect::<Vec<_>>();
 
-        if matching.len() == 0 {
+        let Some(best) = scored.iter().map(|(distance, _)| *distance).min() else {
             return Err(Error::ElementNotFound(val));
-        } else if matching.len() > 1 {
-            return Err(Error::ElementAmbiguous(val));
+        };
+
+        let matching = scored
+            .into_iter()
+            .filter(|(distance, _)| *distance == best)
+            .map(|(_, elem)| elem)
+            .collect::<Vec<_>>();
+
+        match matching.len() {
+            1 => Ok(matching[0]),
+            _ => Err(ambiguous(&val, &matching)),
         }
+    }
 
-        Ok(matching[0])
+    /// Only an exact name or alias, for words that may mean something else.
+    fn get_exact(&self, val: &str) -> Option<&Self::Elem>
+    where
+        Self: Deref<Target = Vec<Self::Elem>>,
+    {
+        let val = normalize_name(val);
+
+        self.iter().find(|elem| {
+            std::iter::once(elem.name())
+                .chain(elem.aliases().iter().map(|alias| alias.as_str()))
+                .any(|name| normalize_name(name) == val)
+        })
+    }
+
+    fn get_by_name(&self, name: &str) -> Result<&Self::Elem>
+    where
+        Self: Deref<Target = Vec<Self::Elem>>,
+    {
+        self.iter()
+            .find(|elem| elem.name() == name)
+            .ok_or(Error::ElementNotFound(name.to_owned()))
     }
 }
 
@@ -210,33 +396,245 @@ impl DerefMut for Messages {
     }
 }
 
+/// A market event from the config, like a cartel war or a big seizure.
+#[derive(Debug)]
+pub struct MarketEvent {
+    pub name: String,
+    /// Drugs the event can hit, any drug when empty.
+    pub drugs: Vec<Arc<Drug>>,
+    pub trend: PriceTrend,
+    /// Chance for the event to start at a location on a given day.
+    pub chance: f64,
+    /// In days.
+    pub duration: u32,
+    pub decay: Decay,
+    /// Radius in km the event spreads to.
+    pub spread: f32,
+}
+
 pub struct GameData {
     pub drugs: Drugs,
     pub items: Items,
     pub locations: Locations,
     pub messages: Messages,
+    pub events: Vec<Arc<MarketEvent>>,
+    pub travel_modes: TravelModes,
+    pub routes: Routes,
 }
 
 impl GameData {
-    pub fn get_matching<E: Element + 'static>(&self, val: &str) -> Result<Arc<E>> {
-        if TypeId::of::<E>() == TypeId::of::<Drug>() {
-            Ok(self.drugs.get_matching(val)?)
+    pub fn get_matching<E: Element>(&self, val: &str) -> Result<Arc<E>> {
+        let elem: Arc<dyn Any + Send + Sync> = if TypeId::of::<E>() == TypeId::of::<Drug>() {
+            self.drugs.get_matching(val)?.clone()
         } else if TypeId::of::<E>() == TypeId::of::<Item>() {
-            Ok(self.items.get_matching(val)?)
+            self.items.get_matching(val)?.clone()
         } else if TypeId::of::<E>() == TypeId::of::<Location>() {
-            Ok(self.locations.get_matching(val)?)
+            self.locations.get_matching(val)?.clone()
+        } else if TypeId::of::<E>() == TypeId::of::<TravelMode>() {
+            self.travel_modes.get_matching(val)?.clone()
         } else {
-            Err(Error::InvalidElement(val.to_string()))
-        }
+            return Err(Error::InvalidElement(val.to_string()));
+        };
+
+        Ok(elem.downcast().unwrap())
     }
 }
 
 pub struct DrugWarsRng(pub StdRng);
 
+impl DrugWarsRng {
+    /// The generator a day starts with. It only depends on the game's seed
+    /// and the date, so replaying the day rolls the same markets.
+    pub fn for_day(seed: u64, day: NaiveDate) -> Self {
+        Self::seeded(seed, day, 0)
+    }
+
+    /// The generator for the market update done when the game starts, kept
+    /// apart from the draws of the day itself.
+    pub fn for_startup(seed: u64, day: NaiveDate) -> Self {
+        Self::seeded(seed, day, 1)
+    }
+
+    fn seeded(seed: u64, day: NaiveDate, stream: u64) -> Self {
+        let mut bytes = [0; 32];
+        bytes[..8].copy_from_slice(&seed.to_le_bytes());
+        bytes[8..12].copy_from_slice(&day.num_days_from_ce().to_le_bytes());
+        bytes[16..24].copy_from_slice(&stream.to_le_bytes());
+
+        Self(StdRng::from_seed(bytes))
+    }
+}
+
+#[derive(Debug)]
+pub struct TravelMode {
+    pub name: String,
+    /// Multiplier over the distance based flight price.
+    pub price_multiplier: f64,
+    /// Distance in km covered in a day.
+    pub speed: f32,
+    /// Chance to get robbed on the way.
+    pub risk: f64,
+    /// Only goes between locations of the same region.
+    pub same_region: bool,
+    /// Only goes between coastal locations.
+    pub coastal: bool,
+}
+
+impl TravelMode {
+    pub fn plane(speed: f32) -> Self {
+        Self {
+            name: "plane".to_owned(),
+            price_multiplier: 1.,
+            speed,
+            risk: 0.,
+            same_region: false,
+            coastal: false,
+        }
+    }
+
+    pub fn available(&self, origin: &Location, destination: &Location) -> bool {
+        (!self.same_region || origin.region == destination.region)
+            && (!self.coastal || (origin.coastal && destination.coastal))
+    }
+}
+
+impl PartialEq for TravelMode {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for TravelMode {}
+
+impl Hash for TravelMode {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+#[derive(Default)]
+pub struct TravelModes(Vec<Arc<TravelMode>>);
+impl Deref for TravelModes {
+    type Target = Vec<Arc<TravelMode>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for TravelModes {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Matching for TravelModes {
+    type Elem = Arc<TravelMode>;
+}
+
+/// Direct connections between locations. Every location is connected to
+/// every other one when no route is configured.
+#[derive(Default)]
+pub struct Routes(HashMap<Arc<Location>, HashSet<Arc<Location>>>);
+
+impl Routes {
+    pub fn connect(&mut self, a: &Arc<Location>, b: &Arc<Location>) {
+        if a == b {
+            return;
+        }
+
+        self.0.entry(a.clone()).or_default().insert(b.clone());
+        self.0.entry(b.clone()).or_default().insert(a.clone());
+    }
+
+    /// Shortest way from `origin` to `destination` with legs served by `mode`,
+    /// as the list of locations to land at, `destination` included.
+    pub fn find<'a>(
+        &'a self,
+        origin: &'a Arc<Location>,
+        destination: &'a Arc<Location>,
+        mode: &TravelMode,
+    ) -> Option<Vec<Arc<Location>>> {
+        if origin == destination {
+            return None;
+        }
+
+        if self.0.is_empty() {
+            return mode
+                .available(origin, destination)
+                .then(|| vec![destination.clone()]);
+        }
+
+        let mut distances: HashMap<&Arc<Location>, (f32, Option<&Arc<Location>>)> =
+            HashMap::from([(origin, (0., None))]);
+        let mut visited = HashSet::new();
+
+        while let Some((current, distance)) = distances
+            .iter()
+            .filter(|(location, _)| !visited.contains(*location))
+            .min_by(|(_, (a, _)), (_, (b, _))| a.total_cmp(b))
+            .map(|(location, (distance, _))| (*location, *distance))
+        {
+            if current == destination {
+                break;
+            }
+
+            visited.insert(current);
+
+            for next in self.0.get(current).into_iter().flatten() {
+                if visited.contains(next) || !mode.available(current, next) {
+                    continue;
+                }
+
+                let next_distance = distance + get_distance(current, next);
+
+                if distances
+                    .get(next)
+                    .is_none_or(|(known, _)| next_distance < *known)
+                {
+                    distances.insert(next, (next_distance, Some(current)));
+                }
+            }
+        }
+
+        let mut route = vec![];
+        let mut current = destination;
+
+        while let Some((_, previous)) = distances.get(current) {
+            route.push(current.clone());
+
+            match previous {
+                Some(previous) => current = previous,
+                None => break,
+            }
+        }
+
+        if current != origin {
+            return None;
+        }
+
+        route.pop();
+        route.reverse();
+
+        Some(route)
+    }
+}
+
+pub struct Flight {
+    pub destination: Arc<Location>,
+    pub mode: Arc<TravelMode>,
+    pub cargo: Cargo,
+    pub arrival: NaiveDate,
+    /// Stops left before `destination`, with the day the dealer lands there.
+    pub layovers: VecDeque<(Arc<Location>, NaiveDate)>,
+    /// Where the dealer is stopped until the next leg leaves.
+    pub layover: Option<Arc<Location>>,
+}
+
 #[derive(Default)]
-pub struct Flights(pub HashMap<String, Arc<Location>>);
+pub struct Flights(pub HashMap<String, Flight>);
 impl Deref for Flights {
-    type Target = HashMap<String, Arc<Location>>;
+    type Target = HashMap<String, Flight>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -0,0 +1,592 @@
+use std::{collections::HashMap, path::Path, sync::Arc, time::SystemTime};
+
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Deserializer, Serialize};
+use tokio::{fs::File, io::AsyncReadExt};
+
+use crate::{
+    config::Settings,
+    customs::CustomsHold,
+    dealer::{Dealer, DealerStatus},
+    element::{Element, MarketElement, OwnedElement},
+    error::Result,
+    location_data::{Decay, PriceMod, PriceModKind, PriceTrend, Rumor},
+    money::Money,
+    preferences::Preferences,
+    resources::{Flight, GameData, Matching},
+    state::GameState,
+};
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedOwnedElement {
+    pub amount: usize,
+    pub bought_at: Money,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedDealer {
+    pub nick: String,
+    pub has_attacked: bool,
+    pub health: f32,
+    pub money: Money,
+    pub laundered_money: Money,
+    pub location: String,
+    pub capacity: usize,
+    pub owned_drugs: HashMap<String, HashMap<String, SavedOwnedElement>>,
+    pub owned_items: HashMap<String, HashMap<String, SavedOwnedElement>>,
+    #[serde(default)]
+    pub carry_on: HashMap<String, SavedOwnedElement>,
+    #[serde(default)]
+    pub customs_hold: Option<SavedCustomsHold>,
+    pub status: DealerStatus,
+    #[serde(default)]
+    pub preferences: Preferences,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedCustomsHold {
+    pub location: String,
+    pub cargo: HashMap<String, SavedOwnedElement>,
+    pub bribe: Money,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct SavedFlight {
+    pub destination: String,
+    /// Missing in saves made before travel modes existed.
+    #[serde(default)]
+    pub mode: Option<String>,
+    /// Missing in saves made before flights took several days, those land
+    /// the next day.
+    #[serde(default)]
+    pub arrival: Option<NaiveDate>,
+    #[serde(default)]
+    pub cargo: HashMap<String, SavedOwnedElement>,
+    #[serde(default)]
+    pub layovers: Vec<(String, NaiveDate)>,
+    #[serde(default)]
+    pub layover: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedPriceMod {
+    pub drug: String,
+    pub trend: PriceTrend,
+    pub kind: PriceModKind,
+    pub duration: u32,
+    pub age: u32,
+    pub decay: Decay,
+    pub strength: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedRumor {
+    pub drug: String,
+    pub trend: PriceTrend,
+    pub location: String,
+    pub confirmed: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedMarketElement {
+    pub supply: usize,
+    pub demand: usize,
+    pub price: Money,
+    pub impact: f64,
+    pub last_trade: SystemTime,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedMarket {
+    pub drugs: HashMap<String, SavedMarketElement>,
+    pub items: HashMap<String, SavedMarketElement>,
+}
+
+fn save_market<E: Element>(
+    market: &HashMap<Arc<E>, MarketElement>,
+) -> HashMap<String, SavedMarketElement> {
+    market
+        .iter()
+        .map(|(elem, market_elem)| {
+            (
+                elem.name().to_owned(),
+                SavedMarketElement {
+                    supply: market_elem.supply,
+                    demand: market_elem.demand,
+                    price: market_elem.price.clone(),
+                    impact: market_elem.impact,
+                    last_trade: market_elem.last_trade,
+                },
+            )
+        })
+        .collect()
+}
+
+fn restore_market<M>(
+    matching: &M,
+    saved: HashMap<String, SavedMarketElement>,
+) -> Result<HashMap<M::Elem, MarketElement>>
+where
+    M: Matching + std::ops::Deref<Target = Vec<M::Elem>>,
+    M::Elem: Clone,
+{
+    let mut market = HashMap::default();
+
+    for (name, saved_elem) in saved {
+        market.insert(
+            matching.get_by_name(&name)?.clone(),
+            MarketElement {
+                supply: saved_elem.supply,
+                demand: saved_elem.demand,
+                price: saved_elem.price,
+                impact: saved_elem.impact,
+                last_trade: saved_elem.last_trade,
+            },
+        );
+    }
+
+    Ok(market)
+}
+
+/// Saves made before flights took several days only kept the destination.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AnySavedFlight {
+    Destination(String),
+    Flight(SavedFlight),
+}
+
+fn deserialize_flights<'de, D>(
+    deserializer: D,
+) -> std::result::Result<HashMap<String, SavedFlight>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let flights = HashMap::<String, AnySavedFlight>::deserialize(deserializer)?;
+
+    Ok(flights
+        .into_iter()
+        .map(|(nick, flight)| {
+            let flight = match flight {
+                AnySavedFlight::Destination(destination) => SavedFlight {
+                    destination,
+                    ..Default::default()
+                },
+                AnySavedFlight::Flight(flight) => flight,
+            };
+
+            (nick, flight)
+        })
+        .collect())
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveData {
+    pub current_day: NaiveDate,
+    /// Missing in saves made before games were seeded.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// How many events of the event log the save covers.
+    #[serde(default)]
+    pub log_position: Option<usize>,
+    /// When the current day started, to catch up on downtime.
+    #[serde(default)]
+    pub day_started: Option<SystemTime>,
+    pub dealers: Vec<SavedDealer>,
+    #[serde(deserialize_with = "deserialize_flights")]
+    pub flights: HashMap<String, SavedFlight>,
+    /// location -> drug -> daily prices
+    pub price_history: HashMap<String, HashMap<String, Vec<Option<Money>>>>,
+    #[serde(default)]
+    pub price_mods: HashMap<String, Vec<SavedPriceMod>>,
+    /// location -> headlines, today first
+    #[serde(default)]
+    pub news: HashMap<String, Vec<Vec<String>>>,
+    /// location -> rumors heard there
+    #[serde(default)]
+    pub rumors: HashMap<String, Vec<SavedRumor>>,
+    /// location -> what's on sale. Missing in saves made before markets
+    /// were kept, those get new markets when loaded.
+    #[serde(default)]
+    pub markets: Option<HashMap<String, SavedMarket>>,
+}
+
+fn save_owned<E: Element>(
+    owned: &HashMap<Arc<E>, OwnedElement>,
+) -> HashMap<String, SavedOwnedElement> {
+    owned
+        .iter()
+        .map(|(elem, owned_elem)| {
+            (
+                elem.name().to_owned(),
+                SavedOwnedElement {
+                    amount: owned_elem.amount,
+                    bought_at: owned_elem.bought_at.clone(),
+                },
+            )
+        })
+        .collect()
+}
+
+fn restore_owned<M>(
+    matching: &M,
+    saved: &HashMap<String, SavedOwnedElement>,
+) -> Result<HashMap<M::Elem, OwnedElement>>
+where
+    M: Matching + std::ops::Deref<Target = Vec<M::Elem>>,
+    M::Elem: Clone,
+{
+    let mut owned = HashMap::default();
+
+    for (name, saved_elem) in saved {
+        owned.insert(
+            matching.get_by_name(name)?.clone(),
+            OwnedElement {
+                amount: saved_elem.amount,
+                bought_at: saved_elem.bought_at.clone(),
+            },
+        );
+    }
+
+    Ok(owned)
+}
+
+impl SaveData {
+    pub fn new(settings: &Settings, state: &GameState, log_position: usize) -> Self {
+        let dealers = state
+            .dealers
+            .values()
+            .map(|dealer| SavedDealer {
+                nick: dealer.nick.clone(),
+                has_attacked: dealer.has_attacked,
+                health: dealer.health,
+                money: dealer.money.clone(),
+                laundered_money: dealer.laundered_money.clone(),
+                location: dealer.location.name.clone(),
+                capacity: dealer.capacity,
+                owned_drugs: dealer
+                    .owned_drugs
+                    .iter()
+                    .map(|(location, owned)| (location.name.clone(), save_owned(owned)))
+                    .collect(),
+                owned_items: dealer
+                    .owned_items
+                    .iter()
+                    .map(|(location, owned)| (location.name.clone(), save_owned(owned)))
+                    .collect(),
+                carry_on: save_owned(&dealer.carry_on),
+                customs_hold: dealer.customs_hold.as_ref().map(|hold| SavedCustomsHold {
+                    location: hold.location.name.clone(),
+                    cargo: save_owned(&hold.cargo),
+                    bribe: hold.bribe.clone(),
+                }),
+                status: dealer.status.clone(),
+                preferences: dealer.preferences.clone(),
+            })
+            .collect();
+
+        let flights = state
+            .flights
+            .iter()
+            .map(|(nick, flight)| {
+                (
+                    nick.clone(),
+                    SavedFlight {
+                        destination: flight.destination.name.clone(),
+                        mode: Some(flight.mode.name.clone()),
+                        arrival: Some(flight.arrival),
+                        cargo: save_owned(&flight.cargo),
+                        layovers: flight
+                            .layovers
+                            .iter()
+                            .map(|(location, day)| (location.name.clone(), *day))
+                            .collect(),
+                        layover: flight
+                            .layover
+                            .as_ref()
+                            .map(|location| location.name.clone()),
+                    },
+                )
+            })
+            .collect();
+
+        let price_history = state
+            .locations
+            .iter()
+            .map(|(location, data)| {
+                let history = data
+                    .price_history
+                    .iter()
+                    .map(|(drug, prices)| (drug.name.clone(), prices.iter().cloned().collect()))
+                    .collect();
+
+                (location.name.clone(), history)
+            })
+            .collect();
+
+        let price_mods = state
+            .locations
+            .iter()
+            .map(|(location, data)| {
+                let price_mods = data
+                    .price_mods
+                    .iter()
+                    .map(|price_mod| SavedPriceMod {
+                        drug: price_mod.drug.name.clone(),
+                        trend: price_mod.trend.clone(),
+                        kind: price_mod.kind.clone(),
+                        duration: price_mod.duration,
+                        age: price_mod.age,
+                        decay: price_mod.decay,
+                        strength: price_mod.strength,
+                    })
+                    .collect();
+
+                (location.name.clone(), price_mods)
+            })
+            .collect();
+
+        let news = state
+            .locations
+            .iter()
+            .map(|(location, data)| {
+                let news = [&data.news]
+                    .into_iter()
+                    .chain(data.news_archive.iter())
+                    .cloned()
+                    .collect();
+
+                (location.name.clone(), news)
+            })
+            .collect();
+
+        let rumors = state
+            .locations
+            .iter()
+            .map(|(location, data)| {
+                let rumors = data
+                    .rumors
+                    .iter()
+                    .map(|rumor| SavedRumor {
+                        drug: rumor.drug.name.clone(),
+                        trend: rumor.trend.clone(),
+                        location: rumor.location.name.clone(),
+                        confirmed: rumor.confirmed,
+                    })
+                    .collect();
+
+                (location.name.clone(), rumors)
+            })
+            .collect();
+
+        let markets = state
+            .locations
+            .iter()
+            .map(|(location, data)| {
+                (
+                    location.name.clone(),
+                    SavedMarket {
+                        drugs: save_market(&data.drug_market),
+                        items: save_market(&data.item_market),
+                    },
+                )
+            })
+            .collect();
+
+        Self {
+            current_day: settings.current_day,
+            seed: Some(settings.seed),
+            log_position: Some(log_position),
+            day_started: Some(settings.timer),
+            dealers,
+            flights,
+            price_history,
+            price_mods,
+            news,
+            rumors,
+            markets: Some(markets),
+        }
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, serde_yaml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Puts the saved state back into a freshly created game state.
+    pub fn restore(
+        self,
+        game_data: &GameData,
+        settings: &mut Settings,
+        state: &mut GameState,
+    ) -> Result<()> {
+        settings.current_day = self.current_day;
+
+        if let Some(seed) = self.seed {
+            settings.seed = seed;
+        }
+
+        if let Some(day_started) = self.day_started {
+            settings.timer = day_started;
+        }
+
+        for saved in self.dealers {
+            let location = game_data.locations.get_by_name(&saved.location)?.clone();
+
+            let mut owned_drugs = HashMap::default();
+            let mut owned_items = HashMap::default();
+
+            for loc in game_data.locations.iter() {
+                let drugs = match saved.owned_drugs.get(&loc.name) {
+                    Some(drugs) => restore_owned(&game_data.drugs, drugs)?,
+                    None => HashMap::default(),
+                };
+                let items = match saved.owned_items.get(&loc.name) {
+                    Some(items) => restore_owned(&game_data.items, items)?,
+                    None => HashMap::default(),
+                };
+
+                owned_drugs.insert(loc.clone(), drugs);
+                owned_items.insert(loc.clone(), items);
+            }
+
+            if saved.status == DealerStatus::Available {
+                let data = state.locations.get_mut(&location).unwrap();
+                data.people.insert(saved.nick.clone());
+            }
+
+            let customs_hold = match saved.customs_hold {
+                Some(hold) => Some(CustomsHold {
+                    location: game_data.locations.get_by_name(&hold.location)?.clone(),
+                    cargo: restore_owned(&game_data.drugs, &hold.cargo)?,
+                    bribe: hold.bribe,
+                }),
+                None => None,
+            };
+
+            state.dealers.insert(
+                saved.nick.clone(),
+                Dealer {
+                    nick: saved.nick,
+                    has_attacked: saved.has_attacked,
+                    health: saved.health,
+                    money: saved.money,
+                    laundered_money: saved.laundered_money,
+                    location,
+                    capacity: saved.capacity,
+                    owned_drugs,
+                    owned_items,
+                    carry_on: restore_owned(&game_data.drugs, &saved.carry_on)?,
+                    customs_hold,
+                    status: saved.status,
+                    preferences: saved.preferences,
+                },
+            );
+        }
+
+        for (nick, flight) in self.flights {
+            let destination = game_data.locations.get_by_name(&flight.destination)?;
+            let mode = match flight.mode {
+                Some(mode) => game_data.travel_modes.get_by_name(&mode)?,
+                None => &game_data.travel_modes[0],
+            };
+            let layovers = flight
+                .layovers
+                .iter()
+                .map(|(location, day)| {
+                    Ok((game_data.locations.get_by_name(location)?.clone(), *day))
+                })
+                .collect::<Result<_>>()?;
+            let layover = match flight.layover {
+                Some(location) => Some(game_data.locations.get_by_name(&location)?.clone()),
+                None => None,
+            };
+
+            state.flights.insert(
+                nick,
+                Flight {
+                    destination: destination.clone(),
+                    mode: mode.clone(),
+                    arrival: flight
+                        .arrival
+                        .unwrap_or(self.current_day + Duration::days(1)),
+                    cargo: restore_owned(&game_data.drugs, &flight.cargo)?,
+                    layovers,
+                    layover,
+                },
+            );
+        }
+
+        for (location, history) in self.price_history {
+            let location = game_data.locations.get_by_name(&location)?;
+            let data = state.locations.get_mut(location).unwrap();
+
+            for (drug, prices) in history {
+                let drug = game_data.drugs.get_by_name(&drug)?;
+                data.price_history
+                    .insert(drug.clone(), prices.into_iter().collect());
+            }
+        }
+
+        for (location, price_mods) in self.price_mods {
+            let location = game_data.locations.get_by_name(&location)?;
+            let data = state.locations.get_mut(location).unwrap();
+
+            for saved in price_mods {
+                data.price_mods.push(PriceMod {
+                    drug: game_data.drugs.get_by_name(&saved.drug)?.clone(),
+                    trend: saved.trend,
+                    kind: saved.kind,
+                    duration: saved.duration,
+                    age: saved.age,
+                    decay: saved.decay,
+                    strength: saved.strength,
+                });
+            }
+        }
+
+        for (location, news) in self.news {
+            let location = game_data.locations.get_by_name(&location)?;
+            let data = state.locations.get_mut(location).unwrap();
+
+            let mut news = news.into_iter();
+            data.news = news.next().unwrap_or_default();
+            data.news_archive = news.collect();
+        }
+
+        for (location, rumors) in self.rumors {
+            let location = game_data.locations.get_by_name(&location)?;
+            let data = state.locations.get_mut(location).unwrap();
+
+            for saved in rumors {
+                data.rumors.push(Rumor {
+                    drug: game_data.drugs.get_by_name(&saved.drug)?.clone(),
+                    trend: saved.trend,
+                    location: game_data.locations.get_by_name(&saved.location)?.clone(),
+                    confirmed: saved.confirmed,
+                });
+            }
+        }
+
+        for (location, market) in self.markets.into_iter().flatten() {
+            let location = game_data.locations.get_by_name(&location)?;
+            let data = state.locations.get_mut(location).unwrap();
+
+            data.drug_market = restore_market(&game_data.drugs, market.drugs)?;
+            data.item_market = restore_market(&game_data.items, market.items)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub async fn load_save(path: impl AsRef<Path>) -> Result<Option<SaveData>> {
+    if !path.as_ref().exists() {
+        return Ok(None);
+    }
+
+    let mut file = File::open(path).await?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).await?;
+    Ok(Some(serde_yaml::from_str(&contents)?))
+}
@@ -0,0 +1,64 @@
+use std::time::{Duration, SystemTime};
+
+use ircie::format::Msg;
+use rand::rngs::StdRng;
+
+use crate::{config::Settings, event_log::EventLog, resources::GameData, state::GameState};
+
+/// How many missed days get played after the bot was down or stalled.
+#[derive(Debug, Clone, Copy)]
+pub enum CatchUp {
+    All,
+    /// At most this many days in a row, the others are dropped.
+    Limit(u32),
+}
+
+/// Everything a rollover hook gets to work with, for the day that just began.
+pub struct DayContext<'a> {
+    pub settings: &'a Settings,
+    pub game_data: &'a GameData,
+    pub state: &'a mut GameState,
+    pub rng: &'a mut StdRng,
+    pub lines: &'a mut Vec<Msg>,
+    pub log: &'a mut EventLog,
+}
+
+/// Runs once per day, in order, every time the day rolls over.
+pub type DayHook = fn(&mut DayContext);
+
+pub struct Rollover {
+    /// Days to play, in order.
+    pub days: u32,
+    /// Missed days dropped by the catch-up policy.
+    pub skipped: u32,
+    /// When the last of those days started.
+    pub day_start: SystemTime,
+}
+
+/// When the next day starts, one `day_duration` after the current one.
+pub fn next_rollover(settings: &Settings) -> SystemTime {
+    settings.timer + Duration::from_secs(settings.day_duration.max(1) as u64)
+}
+
+/// Works out how many days are due at `now`. Days start at exact multiples
+/// of `day_duration` from the current one, so rollovers don't drift.
+pub fn plan_rollover(settings: &Settings, now: SystemTime) -> Option<Rollover> {
+    let day_duration = Duration::from_secs(settings.day_duration.max(1) as u64);
+    let elapsed = now.duration_since(settings.timer).ok()?;
+    let due = (elapsed.as_secs() / day_duration.as_secs()) as u32;
+
+    if due == 0 {
+        return None;
+    }
+
+    let days = match settings.catch_up {
+        CatchUp::All => due,
+        CatchUp::Limit(limit) => due.min(limit.max(1)),
+    };
+
+    Some(Rollover {
+        days,
+        skipped: due - days,
+        day_start: settings.timer + day_duration * due,
+    })
+}
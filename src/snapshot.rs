@@ -0,0 +1,52 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::NaiveDate;
+
+use crate::{
+    config::Settings,
+    error::{Error, Result},
+    save::SaveData,
+};
+
+/// Where the game as it was at the start of `day` is kept.
+pub fn snapshot_path(settings: &Settings, day: NaiveDate) -> PathBuf {
+    PathBuf::from(&settings.snapshot_dir).join(format!("{}.yaml", day.format("%Y-%m-%d")))
+}
+
+/// Keeps the game as it is at the start of the current day, to roll back to
+/// it later.
+pub fn take_snapshot(settings: &Settings, save: &SaveData) -> Result<()> {
+    std::fs::create_dir_all(&settings.snapshot_dir)?;
+    save.write(snapshot_path(settings, settings.current_day))
+}
+
+pub fn load_snapshot(settings: &Settings, day: NaiveDate) -> Result<SaveData> {
+    let path = snapshot_path(settings, day);
+
+    if !path.exists() {
+        return Err(Error::NoSnapshot(day.format("%Y-%m-%d").to_string()));
+    }
+
+    Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+/// Keeps the game a rollback is about to undo, next to the snapshots.
+pub fn keep_rolled_back(settings: &Settings, save: &SaveData) -> Result<PathBuf> {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = PathBuf::from(&settings.snapshot_dir).join(format!(
+        "rolled_back_{}_{}.yaml",
+        settings.current_day.format("%Y-%m-%d"),
+        secs
+    ));
+
+    std::fs::create_dir_all(&settings.snapshot_dir)?;
+    save.write(&path)?;
+
+    Ok(path)
+}
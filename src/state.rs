@@ -0,0 +1,53 @@
+use crate::{
+    dealer::{Dealer, Dealers},
+    error::Result,
+    location_data::{LocationData, SingleLocationData},
+    resources::{Flights, Locations},
+};
+
+/// Everything the players change, owned in one place.
+///
+/// It's a single resource, so a command gets all of it for as long as it
+/// runs: changes touching several dealers, locations or flights land
+/// together, and a save always sees the game at one instant.
+#[derive(Default)]
+pub struct GameState {
+    pub dealers: Dealers,
+    pub locations: LocationData,
+    pub flights: Flights,
+}
+
+impl GameState {
+    /// A game nobody plays yet.
+    pub fn new(locations: &Locations) -> Self {
+        let mut state = Self::default();
+
+        for location in locations.iter() {
+            state
+                .locations
+                .insert(location.clone(), SingleLocationData::default());
+        }
+
+        state
+    }
+
+    /// A dealer and the location they're in.
+    pub fn dealer_location(&self, nick: &str) -> Result<(&Dealer, &SingleLocationData)> {
+        let dealer = self.dealers.get_dealer(nick)?;
+        let data = self.locations.get(&dealer.location).unwrap();
+
+        Ok((dealer, data))
+    }
+
+    /// An available dealer and the location they're in, to do business
+    /// there.
+    pub fn dealer_location_mut(
+        &mut self,
+        nick: &str,
+    ) -> Result<(&mut Dealer, &mut SingleLocationData)> {
+        let dealer = self.dealers.get_dealer_available_mut(nick)?;
+        let data = self.locations.get_mut(&dealer.location).unwrap();
+
+        Ok((dealer, data))
+    }
+}
@@ -1,10 +1,15 @@
-use std::{f32::consts::PI, path::Path, str};
+use std::{f32::consts::PI, path::Path, str, sync::Arc};
 
 use chrono::Duration;
-use num_bigint::{BigInt, ToBigInt};
+use itertools::Itertools;
 use tokio::{fs::File, io::AsyncReadExt};
 
-use crate::{config::{DrugWarsConfig, Settings}, resources::Location};
+use crate::{
+    config::{DrugWarsConfig, Settings},
+    error::{Error, Result},
+    money::Money,
+    resources::{Location, TravelMode},
+};
 
 pub async fn load_config(path: impl AsRef<Path>) -> std::io::Result<DrugWarsConfig> {
     let mut file = File::open(path).await?;
@@ -13,49 +18,205 @@ pub async fn load_config(path: impl AsRef<Path>) -> std::io::Result<DrugWarsConf
     Ok(serde_yaml::from_str(&contents).unwrap())
 }
 
-pub fn get_flight_price(origin: &Location, other: &Location) -> BigInt {
+pub const EARTH_RADIUS: f32 = 6371.;
+
+/// Great-circle distance in km.
+pub fn get_distance(origin: &Location, other: &Location) -> f32 {
     let cur_lat = origin.position.lat * (PI / 180.);
     let cur_long = origin.position.long * (PI / 180.);
 
     let other_lat = other.position.lat * (PI / 180.);
     let other_long = other.position.long * (PI / 180.);
 
-    let float_price = (cur_lat.sin() * other_lat.sin()
+    (cur_lat.sin() * other_lat.sin()
         + cur_lat.cos() * other_lat.cos() * (other_long - cur_long).cos())
+    .clamp(-1., 1.)
     .acos()
-        * 10000.;
+        * EARTH_RADIUS
+}
 
-    (float_price * 10000.).to_bigint().unwrap()
+pub fn get_flight_price(origin: &Location, other: &Location) -> Money {
+    Money::from_dollars((get_distance(origin, other) / EARTH_RADIUS * 10000.) as f64)
 }
 
-pub trait PrettyMoney {
-    fn pretty_money(&self) -> String;
+pub fn get_travel_price(origin: &Location, other: &Location, mode: &TravelMode) -> Money {
+    get_flight_price(origin, other).scale(mode.price_multiplier)
 }
 
-pub trait PrettyAmount {
-    fn pretty_amount(&self) -> String;
+/// How many days a trip between two locations takes, at least one.
+pub fn get_travel_days(origin: &Location, other: &Location, mode: &TravelMode) -> i64 {
+    ((get_distance(origin, other) / mode.speed.max(1.)).ceil() as i64).max(1)
 }
 
-impl PrettyMoney for BigInt {
-    fn pretty_money(&self) -> String {
-        let unit: BigInt = self.clone() / 10000;
-        let dec: BigInt = self % 10000;
+fn route_legs<'a>(
+    origin: &'a Location,
+    route: &'a [Arc<Location>],
+) -> impl Iterator<Item = (&'a Location, &'a Location)> {
+    std::iter::once(origin)
+        .chain(route.iter().map(|location| location.as_ref()))
+        .tuple_windows()
+}
 
-        let pretty_money = unit
-            .to_string()
-            .as_bytes()
-            .rchunks(3)
-            .rev()
-            .map(str::from_utf8)
-            .collect::<std::result::Result<Vec<&str>, _>>()
-            .unwrap()
-            .join(",");
+pub fn get_route_price(origin: &Location, route: &[Arc<Location>], mode: &TravelMode) -> Money {
+    route_legs(origin, route)
+        .map(|(from, to)| get_travel_price(from, to, mode))
+        .sum()
+}
+
+/// Days spent on each leg of the route.
+pub fn get_route_days(origin: &Location, route: &[Arc<Location>], mode: &TravelMode) -> Vec<i64> {
+    route_legs(origin, route)
+        .map(|(from, to)| get_travel_days(from, to, mode))
+        .collect()
+}
+
+/// Lowercases a name and drops anything that isn't a letter or a digit.
+pub fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Levenshtein distance between two strings.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous + (a_char != *b_char) as usize;
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// How much of something a player asked for, resolved by each command
+/// against what it allows.
+#[derive(Debug, Clone)]
+pub enum Amount {
+    Units(usize),
+    /// `all` or `max`, as much as the command allows.
+    Max,
+    /// A share of `Max`, between 0 and 1.
+    Percent(f64),
+    /// As many units as this much money covers.
+    Money(Money),
+}
+
+impl Amount {
+    /// Turns the amount into units. `max` is the most the command allows and
+    /// `cost` gives the total for a number of units.
+    pub fn resolve(&self, max: usize, cost: impl Fn(usize) -> Money) -> Result<usize> {
+        let units = match self {
+            Amount::Units(units) => *units,
+            Amount::Max => max,
+            Amount::Percent(percent) => (max as f64 * percent).floor() as usize,
+            Amount::Money(money) => max_affordable(max, money, cost),
+        };
+
+        match units {
+            0 => Err(Error::AmountTooSmall),
+            _ => Ok(units),
+        }
+    }
+}
+
+/// Reads `1,000`, `1.5k`, `2m` or `3b`.
+pub fn parse_number(val: &str) -> Option<f64> {
+    let val = val.replace(',', "");
+
+    let (number, multiplier) = match val.chars().last()? {
+        'k' => (&val[..val.len() - 1], 1e3),
+        'm' => (&val[..val.len() - 1], 1e6),
+        'b' => (&val[..val.len() - 1], 1e9),
+        _ => (val.as_str(), 1.),
+    };
+
+    let number = number.parse::<f64>().ok()?;
 
-        let dec_str = format!("{:0>2}", dec);
-        format!("${}.{:0>2}", pretty_money, &dec_str[..2])
+    (number.is_finite() && number >= 0.).then_some(number * multiplier)
+}
+
+/// Parses `10`, `1.5k`, `50%`, `all`, `max` or `$1,000`.
+pub fn parse_amount(val: &str) -> Result<Amount> {
+    let lower = val.to_lowercase();
+    let invalid = || Error::InvalidAmount(val.to_owned());
+
+    if lower == "all" || lower == "max" {
+        return Ok(Amount::Max);
+    }
+
+    if lower.starts_with('$') {
+        return Ok(Amount::Money(lower.parse()?));
+    }
+
+    if let Some(percent) = lower.strip_suffix('%') {
+        let percent = percent.parse::<f64>().map_err(|_| invalid())?;
+
+        if !(percent > 0. && percent <= 100.) {
+            return Err(invalid());
+        }
+
+        return Ok(Amount::Percent(percent / 100.));
+    }
+
+    match parse_number(&lower) {
+        Some(units) if units >= 1. && units.fract() == 0. => Ok(Amount::Units(units as usize)),
+        _ => Err(invalid()),
     }
 }
 
+/// The most units up to `limit` whose total `cost` fits in `budget`, with
+/// `cost` growing with the number of units.
+pub fn max_affordable(limit: usize, budget: &Money, cost: impl Fn(usize) -> Money) -> usize {
+    let (mut low, mut high) = (0, limit);
+
+    while low < high {
+        let middle = low + (high - low).div_ceil(2);
+
+        if &cost(middle) <= budget {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+
+    low
+}
+
+/// Splits `<element> <amount> [price limit]` trade arguments.
+pub fn parse_trade_arguments<'a>(
+    arguments: &[&'a str],
+) -> Result<(&'a str, Amount, Option<Money>)> {
+    if arguments.len() < 2 || arguments.len() > 3 {
+        return Err(Error::InvalidArguments);
+    }
+
+    let amount = parse_amount(arguments[1])?;
+
+    let limit = match arguments.get(2) {
+        Some(limit) => Some(limit.parse()?),
+        None => None,
+    };
+
+    Ok((arguments[0], amount, limit))
+}
+
+pub trait PrettyMoney {
+    fn pretty_money(&self) -> String;
+}
+
+pub trait PrettyAmount {
+    fn pretty_amount(&self) -> String;
+}
+
 impl PrettyAmount for usize {
     fn pretty_amount(&self) -> String {
         self.to_string()
@@ -72,6 +233,8 @@ impl PrettyAmount for usize {
 pub trait StringManips {
     fn irc_safe_len(&self) -> usize;
     fn pretty_truncate(&self, max_width: usize) -> String;
+    /// Drops colours, bold and the like.
+    fn strip_formatting(&self) -> String;
 }
 
 impl<T: std::fmt::Display> StringManips for T {
@@ -86,6 +249,10 @@ impl<T: std::fmt::Display> StringManips for T {
     }
 
     fn irc_safe_len(&self) -> usize {
+        self.strip_formatting().chars().count()
+    }
+
+    fn strip_formatting(&self) -> String {
         self.to_string()
             .replace("\x0300", "")
             .replace("\x0301", "")
@@ -105,7 +272,7 @@ impl<T: std::fmt::Display> StringManips for T {
             .replace("\x0315", "")
             .chars()
             .filter(|c| !['\x02', '\x1d', '\x1f', '\x1e', '\x12', '\x0f'].contains(c))
-            .count()
+            .collect()
     }
 }
 